@@ -0,0 +1,91 @@
+//! Measures encoding a comment header with several large pictures, comparing the single-pass
+//! encoder used by `Tag::build_stream` with the previous encoder, which formatted every comment
+//! into its own `String` before copying it into the output.
+//!
+//! Run with `cargo bench --bench encode_comment_header`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use opusmeta::{OpusHead, Tag};
+
+/// Counts the bytes requested from the system allocator.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+// SAFETY: every call is forwarded to the system allocator unchanged.
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        // SAFETY: the caller upholds the contract of `GlobalAlloc::alloc`.
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: the caller upholds the contract of `GlobalAlloc::dealloc`.
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const ITERATIONS: u32 = 20;
+
+/// The comment header encoder before it was changed to a single pass.
+fn encode_formatted(tag: &Tag) -> Vec<u8> {
+    let mut output = vec![];
+    output.extend_from_slice(b"OpusTags");
+
+    let vendor = tag.get_vendor();
+    output.extend_from_slice(&u32::try_from(vendor.len()).unwrap().to_le_bytes());
+    output.extend_from_slice(vendor.as_bytes());
+
+    let formatted_tags = tag.comment_lines();
+    output.extend_from_slice(&u32::try_from(formatted_tags.len()).unwrap().to_le_bytes());
+    for tag in formatted_tags {
+        output.extend_from_slice(&u32::try_from(tag.len()).unwrap().to_le_bytes());
+        output.extend_from_slice(tag.as_bytes());
+    }
+    output
+}
+
+/// Runs `f` repeatedly, and prints the average time and number of bytes allocated per run.
+fn measure(name: &str, mut f: impl FnMut()) {
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+    let allocated = (ALLOCATED.load(Ordering::Relaxed) - allocated) / ITERATIONS as usize;
+    println!(
+        "{name:<24} {elapsed:>12.2?} {:>10} KiB allocated",
+        allocated / 1024
+    );
+}
+
+fn main() {
+    let mut tag = Tag::new("opusmeta".into(), vec![]);
+    tag.add_one("title".into(), "Silence".into());
+    tag.add_many("artist".into(), vec!["A".into(), "B".into()]);
+    // four pictures of 4 MiB each, roughly the size of a high resolution cover once encoded
+    for _ in 0..4 {
+        tag.add_one("metadata_block_picture".into(), "A".repeat(4 << 20));
+    }
+
+    let head = OpusHead::default();
+    println!("comment header of {} KiB", tag.encoded_len() / 1024);
+    measure("formatted (previous)", || {
+        black_box(encode_formatted(black_box(&tag)));
+    });
+    // this includes splitting the comment header into ogg pages, which the previous encoder
+    // didn't do
+    measure("single pass (current)", || {
+        tag.build_stream(&head, std::iter::empty(), std::io::sink())
+            .unwrap();
+    });
+}
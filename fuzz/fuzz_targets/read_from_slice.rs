@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use opusmeta::Tag;
+
+fuzz_target!(|data: &[u8]| {
+    // the comment header parser is also fuzzed on its own, without the ogg layer around it
+    let _ = Tag::from_comment_header(data);
+
+    if let Ok(tag) = Tag::read_from_slice(data) {
+        // decoding the pictures must not panic either
+        let _ = tag.pictures();
+    }
+});
@@ -0,0 +1,185 @@
+use std::ops::Range;
+
+use crate::iter::{CommentRangesIterator, ParsedCommentsIterator};
+use crate::{Error, ReadOptions, Result};
+
+/// A comment header packet which was parsed without copying any of its contents.
+///
+/// All of the strings returned by this type borrow from the packet it was parsed from. See
+/// [`parse_comment_header`] for more info.
+#[derive(Debug, Clone)]
+pub struct ParsedHeader<'a> {
+    packet: &'a [u8],
+    vendor: &'a str,
+    comments_start: usize,
+    comment_count: u32,
+    comments_end: usize,
+}
+
+impl<'a> ParsedHeader<'a> {
+    /// Gets the vendor string.
+    #[must_use]
+    pub const fn vendor(&self) -> &'a str {
+        self.vendor
+    }
+
+    /// Gets the number of comments in the header, including pictures.
+    #[must_use]
+    pub const fn comment_count(&self) -> u32 {
+        self.comment_count
+    }
+
+    /// Gets the data following the comments, which is empty for most files. Per the spec, this
+    /// is padding if the lowest bit of its first byte is clear, and binary data to be preserved
+    /// otherwise.
+    #[must_use]
+    pub fn trailing_data(&self) -> &'a [u8] {
+        &self.packet[self.comments_end..]
+    }
+
+    /// An iterator over the comments in the header, including pictures, in the order in which
+    /// they appear.
+    ///
+    /// See [`ParsedCommentsIterator`] for more info.
+    #[must_use]
+    pub const fn comments(&self) -> ParsedCommentsIterator<'a> {
+        ParsedCommentsIterator {
+            ranges: self.comment_ranges(),
+        }
+    }
+
+    /// An iterator over the byte ranges of the keys and values of the comments within the packet,
+    /// in the order in which they appear. This is useful for editing the packet in place.
+    ///
+    /// See [`CommentRangesIterator`] for more info.
+    #[must_use]
+    pub const fn comment_ranges(&self) -> CommentRangesIterator<'a> {
+        CommentRangesIterator {
+            packet: self.packet,
+            position: self.comments_start,
+            index: 0,
+            count: self.comment_count,
+        }
+    }
+}
+
+/// Parses a comment header packet (starting with `OpusTags`) without allocating.
+///
+/// Unlike [`Tag::read_from`](crate::Tag::read_from), the keys are not lowercased, and the comments
+/// are kept in the order in which they appear in the packet. The whole packet is validated up
+/// front, so iterating over the comments of the returned [`ParsedHeader`] can't fail.
+/// # Errors
+/// This function can error if:
+/// - The packet is shorter than mandated by the spec
+/// - The platform's usize is not at least 32 bits long
+/// - The spec mandates UTF-8, but the data is invalid unicode
+/// - A comment line is not in TAG=VALUE format.
+pub fn parse_comment_header(packet: &[u8]) -> Result<ParsedHeader<'_>> {
+    parse(packet, &ReadOptions::default())
+}
+
+pub fn parse<'a>(packet: &'a [u8], options: &ReadOptions) -> Result<ParsedHeader<'a>> {
+    let (vendor, mut position) = parse_vendor(packet)?;
+
+    let comment_count = packet
+        .get(position..position + 4)
+        .ok_or(Error::TruncatedCommentCount)?;
+    let comment_count = read_u32(comment_count);
+    position += 4;
+    let comments_start = position;
+
+    for index in 0..comment_count {
+        let range = comment_at(packet, position, index)?;
+        position = range.end;
+        let comment = comment_to_str(&packet[range], index)?;
+        if !options.allow_bare_keys && !comment.contains('=') {
+            return Err(Error::MalformedComment(comment.to_string()));
+        }
+    }
+
+    Ok(ParsedHeader {
+        packet,
+        vendor,
+        comments_start,
+        comment_count,
+        comments_end: position,
+    })
+}
+
+/// Parses the vendor string at the start of a comment header packet. Returns the vendor string
+/// and the position of the comment count.
+pub fn parse_vendor(packet: &[u8]) -> Result<(&str, usize)> {
+    // skip the magic signature "OpusTags"
+    let vendor_length = packet.get(8..12).ok_or(Error::TruncatedVendor)?;
+    // only panics on platforms where usize < 32 bits
+    let vendor_length: usize = read_u32(vendor_length).try_into()?;
+    let end = 12_usize
+        .checked_add(vendor_length)
+        .filter(|&end| end <= packet.len())
+        .ok_or(Error::TruncatedVendor)?;
+    Ok((to_str(&packet[12..end])?, end))
+}
+
+/// Checks whether any comment has the given key, compared case-insensitively. Returns as soon as
+/// a match is found, and doesn't validate the comments it skips over.
+pub fn has_key(packet: &[u8], key: &str) -> Result<bool> {
+    let (_, mut position) = parse_vendor(packet)?;
+    let comment_count = packet
+        .get(position..position + 4)
+        .ok_or(Error::TruncatedCommentCount)?;
+    let comment_count = read_u32(comment_count);
+    position += 4;
+
+    for index in 0..comment_count {
+        let range = comment_at(packet, position, index)?;
+        position = range.end;
+        let comment = &packet[range];
+        if comment.len() > key.len()
+            && comment[key.len()] == b'='
+            && comment[..key.len()].eq_ignore_ascii_case(key.as_bytes())
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Returns the byte range of the comment whose length prefix starts at `position`.
+pub fn comment_at(packet: &[u8], position: usize, index: u32) -> Result<Range<usize>> {
+    let start = position + 4;
+    let comment_length = packet
+        .get(position..start)
+        .ok_or(Error::TruncatedComment { index })?;
+    // only panics on platforms where usize < 32 bits
+    let comment_length: usize = read_u32(comment_length).try_into()?;
+    let end = start
+        .checked_add(comment_length)
+        .filter(|&end| end <= packet.len())
+        .ok_or(Error::TruncatedComment { index })?;
+    Ok(start..end)
+}
+
+pub fn to_str(bytes: &[u8]) -> Result<&str> {
+    std::str::from_utf8(bytes).map_err(|_| {
+        String::from_utf8(bytes.to_vec())
+            .expect_err("the bytes were just found to be invalid UTF-8")
+            .into()
+    })
+}
+
+/// Like [`to_str`], but reports the index and the key of the comment if it is not valid UTF-8.
+pub fn comment_to_str(bytes: &[u8], index: u32) -> Result<&str> {
+    std::str::from_utf8(bytes).map_err(|_| {
+        let key = bytes.split(|&byte| byte == b'=').next().unwrap_or_default();
+        Error::CommentUTFError {
+            index,
+            key: std::str::from_utf8(key).ok().map(ToString::to_string),
+            error: String::from_utf8(bytes.to_vec())
+                .expect_err("the bytes were just found to be invalid UTF-8"),
+        }
+    })
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes.try_into().expect("slice has length 4"))
+}
@@ -0,0 +1,242 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+/// A map from lowercase keys to their values, which remembers the order in which the keys were
+/// first inserted.
+///
+/// Two maps are equal if they contain the same keys with the same values, regardless of the order
+/// of the keys.
+#[derive(Clone, Default)]
+pub struct CommentMap {
+    entries: Vec<(String, Vec<String>)>,
+    indices: HashMap<String, usize>,
+}
+
+impl CommentMap {
+    pub fn get(&self, key: &str) -> Option<&Vec<String>> {
+        self.indices.get(key).map(|&index| &self.entries[index].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Vec<String>> {
+        self.indices
+            .get(key)
+            .map(|&index| &mut self.entries[index].1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    /// Gets the values of a key, inserting the key with no values after all other keys if it
+    /// doesn't exist yet.
+    pub fn get_or_default(&mut self, key: String) -> &mut Vec<String> {
+        let index = self.indices.get(&key).copied().unwrap_or_else(|| {
+            let index = self.entries.len();
+            self.indices.insert(key.clone(), index);
+            self.entries.push((key, Vec::new()));
+            index
+        });
+        &mut self.entries[index].1
+    }
+
+    /// Gets the entry of a key for in-place manipulation.
+    pub const fn entry(&mut self, key: String) -> Entry<'_> {
+        Entry { map: self, key }
+    }
+
+    /// Replaces the values of a key, keeping its position, or inserts it after all other keys.
+    /// Returns the replaced values, if any.
+    pub fn insert(&mut self, key: String, values: Vec<String>) -> Option<Vec<String>> {
+        if let Some(&index) = self.indices.get(&key) {
+            return Some(std::mem::replace(&mut self.entries[index].1, values));
+        }
+        self.indices.insert(key.clone(), self.entries.len());
+        self.entries.push((key, values));
+        None
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Vec<String>> {
+        let index = self.indices.remove(key)?;
+        let (_, values) = self.entries.remove(index);
+        for (key, _) in &self.entries[index..] {
+            if let Some(index) = self.indices.get_mut(key) {
+                *index -= 1;
+            }
+        }
+        Some(values)
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&str, &mut Vec<String>) -> bool) {
+        self.entries.retain_mut(|(key, values)| f(key, values));
+        self.reindex();
+    }
+
+    /// Reorders the keys using a comparator. The values of every key keep their order.
+    pub fn sort_by(&mut self, mut cmp: impl FnMut(&str, &str) -> Ordering) {
+        self.entries.sort_by(|(a, _), (b, _)| cmp(a, b));
+        self.reindex();
+    }
+
+    fn reindex(&mut self) {
+        self.indices = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, (key, _))| (key.clone(), index))
+            .collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.indices.clear();
+    }
+
+    /// An iterator over the keys and their values, in insertion order.
+    pub fn iter(&self) -> std::slice::Iter<'_, (String, Vec<String>)> {
+        self.entries.iter()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+}
+
+/// A view into the values of a single key of a [`Tag`](crate::Tag), which may not exist yet.
+/// Created by [`Tag::entry`](crate::Tag::entry).
+pub struct Entry<'a> {
+    map: &'a mut CommentMap,
+    key: String,
+}
+
+impl<'a> Entry<'a> {
+    /// Gets the key of the entry.
+    #[must_use]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Inserts the given values if the key doesn't exist yet, and returns the values of the key.
+    #[must_use]
+    pub fn or_insert(self, default: Vec<String>) -> &'a mut Vec<String> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the result of the function if the key doesn't exist yet, and returns the values of
+    /// the key.
+    pub fn or_insert_with(self, default: impl FnOnce() -> Vec<String>) -> &'a mut Vec<String> {
+        if !self.map.contains_key(&self.key) {
+            self.map.insert(self.key.clone(), default());
+        }
+        self.map.get_or_default(self.key)
+    }
+
+    /// Inserts the key with no values if it doesn't exist yet, and returns the values of the key.
+    #[must_use]
+    pub fn or_default(self) -> &'a mut Vec<String> {
+        self.map.get_or_default(self.key)
+    }
+
+    /// Modifies the values of the key if it exists.
+    #[must_use]
+    pub fn and_modify(self, f: impl FnOnce(&mut Vec<String>)) -> Self {
+        if let Some(values) = self.map.get_mut(&self.key) {
+            f(values);
+        }
+        self
+    }
+}
+
+impl PartialEq for CommentMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries.len() == other.entries.len()
+            && self
+                .entries
+                .iter()
+                .all(|(key, values)| other.get(key) == Some(values))
+    }
+}
+
+impl Eq for CommentMap {}
+
+impl Debug for CommentMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.entries.iter().map(|(key, values)| (key, values)))
+            .finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a CommentMap {
+    type Item = &'a (String, Vec<String>);
+    type IntoIter = std::slice::Iter<'a, (String, Vec<String>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for CommentMap {
+    type Item = (String, Vec<String>);
+    type IntoIter = std::vec::IntoIter<(String, Vec<String>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sort_keys() {
+        let mut map = CommentMap::default();
+        for key in ["title", "artist", "album"] {
+            map.insert(key.to_string(), vec![key.to_uppercase()]);
+        }
+        map.sort_by(Ord::cmp);
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["album", "artist", "title"]);
+        assert_eq!(map.get("title").unwrap(), &["TITLE"]);
+        assert_eq!(map.get("artist").unwrap(), &["ARTIST"]);
+    }
+
+    #[test]
+    fn keeps_insertion_order() {
+        let mut map = CommentMap::default();
+        for key in ["title", "artist", "album", "date"] {
+            map.get_or_default(key.into()).push(key.to_uppercase());
+        }
+        map.remove("artist");
+        map.insert("title".into(), vec!["Silence".into()]);
+        map.insert("genre".into(), vec!["Ambient".into()]);
+
+        let keys: Vec<&String> = map.keys().collect();
+        assert_eq!(keys, ["title", "album", "date", "genre"]);
+        assert_eq!(map.get("date"), Some(&vec!["DATE".to_string()]));
+        assert_eq!(map.get("genre"), Some(&vec!["Ambient".to_string()]));
+        assert_eq!(map.get("artist"), None);
+
+        map.retain(|key, _| key != "album");
+        map.get_or_default("date".into()).push("2024".into());
+        assert_eq!(
+            map.get("date"),
+            Some(&vec!["DATE".to_string(), "2024".to_string()])
+        );
+        assert_eq!(map.keys().count(), 3);
+    }
+
+    #[test]
+    fn equality_ignores_order() {
+        let mut first = CommentMap::default();
+        first.insert("title".into(), vec!["A".into()]);
+        first.insert("artist".into(), vec!["B".into()]);
+        let mut second = CommentMap::default();
+        second.insert("artist".into(), vec!["B".into()]);
+        second.insert("title".into(), vec!["A".into()]);
+        assert_eq!(first, second);
+
+        second.insert("title".into(), vec!["C".into()]);
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::Read;
+
+use crate::{Error, Result, comment_header};
+
+/// Reads the comments of a comment header packet one at a time, without reading the whole packet
+/// into memory.
+///
+/// The reader must be positioned at the start of the packet (i.e. at the `OpusTags` signature),
+/// and must only contain the packet data, not the ogg pages around it. The comments are yielded
+/// in the order in which they appear, including pictures, and the keys are not lowercased. This
+/// is useful for searching huge comment headers for a single key, since iteration can stop as
+/// soon as the key is found.
+///
+/// The iterator Item is `Result<(String, String)>`. After an error, the iterator ends.
+pub struct CommentStream<R> {
+    reader: R,
+    vendor: String,
+    remaining: u32,
+    index: u32,
+}
+
+impl<R: Read> CommentStream<R> {
+    /// Reads the signature, the vendor string and the comment count from the start of a comment
+    /// header packet.
+    /// # Errors
+    /// This function can error if:
+    /// - The packet doesn't start with `OpusTags`
+    /// - The packet ends before the comment count
+    /// - The vendor string is not valid UTF-8
+    /// - An error occurs while reading from the reader
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != b"OpusTags" {
+            return Err(Error::NotOpus);
+        }
+
+        let vendor_length = read_u32(&mut reader).map_err(|_| Error::TruncatedVendor)?;
+        let vendor = read_string(&mut reader, vendor_length).ok_or(Error::TruncatedVendor)?;
+        let vendor = String::from_utf8(vendor)?;
+        let remaining = read_u32(&mut reader).map_err(|_| Error::TruncatedCommentCount)?;
+
+        Ok(Self {
+            reader,
+            vendor,
+            remaining,
+            index: 0,
+        })
+    }
+
+    /// Gets the vendor string.
+    #[must_use]
+    pub fn vendor(&self) -> &str {
+        &self.vendor
+    }
+
+    /// Gets the number of comments which haven't been read yet.
+    #[must_use]
+    pub const fn remaining(&self) -> u32 {
+        self.remaining
+    }
+
+    fn read_comment(&mut self) -> Result<(String, String)> {
+        let index = self.index;
+        let length = read_u32(&mut self.reader).map_err(|_| Error::TruncatedComment { index })?;
+        let comment =
+            read_string(&mut self.reader, length).ok_or(Error::TruncatedComment { index })?;
+        let comment = comment_header::comment_to_str(&comment, index)?.to_string();
+        let Some((key, value)) = comment.split_once('=') else {
+            return Err(Error::MalformedComment(comment));
+        };
+        Ok((key.to_string(), value.to_string()))
+    }
+}
+
+impl<R: Read> Iterator for CommentStream<R> {
+    type Item = Result<(String, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let comment = self.read_comment();
+        self.index += 1;
+        self.remaining = if comment.is_ok() {
+            self.remaining - 1
+        } else {
+            0
+        };
+        Some(comment)
+    }
+}
+
+fn read_u32<R: Read>(mut reader: R) -> std::io::Result<u32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+/// Reads exactly `length` bytes, without allocating more than was actually read. Returns None if
+/// the reader ends early or fails.
+fn read_string<R: Read>(reader: R, length: u32) -> Option<Vec<u8>> {
+    let mut buffer = Vec::new();
+    reader
+        .take(length.into())
+        .read_to_end(&mut buffer)
+        .ok()
+        .filter(|&read| read as u64 == u64::from(length))?;
+    Some(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::tests::comment_header;
+
+    #[test]
+    fn stop_early() {
+        let data = comment_header(
+            "opusmeta",
+            &[b"TITLE=Silence", b"ARTIST=A", b"ALBUM=Quiet", b"DATE=2024"],
+        );
+        let mut stream = CommentStream::new(Cursor::new(&data)).unwrap();
+        assert_eq!(stream.vendor(), "opusmeta");
+        assert_eq!(stream.remaining(), 4);
+
+        let artist = stream
+            .by_ref()
+            .map(Result::unwrap)
+            .find(|(key, _)| key.eq_ignore_ascii_case("artist"));
+        assert_eq!(artist, Some(("ARTIST".to_string(), "A".to_string())));
+        assert_eq!(stream.remaining(), 2);
+        // the rest of the packet wasn't read
+        assert!(stream.reader.position() < data.len() as u64);
+    }
+
+    #[test]
+    fn stop_after_error() {
+        let data = comment_header(
+            "opusmeta",
+            &[b"TITLE=Silence", b"NOTACOMMENT", b"ALBUM=Quiet"],
+        );
+        let comments: Vec<_> = CommentStream::new(Cursor::new(&data)).unwrap().collect();
+        assert_eq!(comments.len(), 2);
+        assert!(matches!(comments[1], Err(Error::MalformedComment(_))));
+
+        // a comment length past the end of the packet doesn't allocate the claimed length
+        let mut data = comment_header("opusmeta", &[b"TITLE=Silence", b"ALBUM=Quiet"]);
+        data.truncate(data.len() - 15);
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        let comments: Vec<_> = CommentStream::new(Cursor::new(&data)).unwrap().collect();
+        assert_eq!(comments.len(), 2);
+        assert!(matches!(
+            comments[1],
+            Err(Error::TruncatedComment { index: 1 })
+        ));
+
+        assert!(matches!(
+            CommentStream::new(Cursor::new(b"OpusHead")),
+            Err(Error::NotOpus)
+        ));
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use crate::picture::Picture;
+use crate::{PICTURE_BLOCK_TAG, Tag};
+
+/// The differences between two [`Tag`]s, as reported by [`Tag::diff`] and
+/// [`Tag::diff_against_file`].
+///
+/// Keys are lowercase and sorted. Printing a `TagDiff` lists one change per line, e.g.
+/// `+ title=Silence` for an added key.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TagDiff {
+    /// The old and the new vendor string, if they differ.
+    pub vendor: Option<(String, String)>,
+    /// Keys which only exist in the new tag, with their values.
+    pub added: Vec<(String, Vec<String>)>,
+    /// Keys which only exist in the old tag, with their values.
+    pub removed: Vec<(String, Vec<String>)>,
+    /// Keys which exist in both tags, but with different values.
+    pub changed: Vec<KeyChange>,
+    /// The encoded pictures which only exist in the new tag. See
+    /// [`added_pictures`](Self::added_pictures).
+    pub added_pictures: Vec<String>,
+    /// The encoded pictures which only exist in the old tag. See
+    /// [`removed_pictures`](Self::removed_pictures).
+    pub removed_pictures: Vec<String>,
+}
+
+/// A key whose values differ between two [`Tag`]s. See [`TagDiff`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct KeyChange {
+    pub key: String,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl TagDiff {
+    pub(crate) fn new(old: &Tag, new: &Tag) -> Self {
+        let mut diff = Self::default();
+        if old.vendor != new.vendor {
+            diff.vendor = Some((old.vendor.clone(), new.vendor.clone()));
+        }
+
+        let keys: BTreeSet<&String> = old.comments.keys().chain(new.comments.keys()).collect();
+        for key in keys {
+            if key == PICTURE_BLOCK_TAG {
+                continue;
+            }
+            match (old.comments.get(key), new.comments.get(key)) {
+                (Some(old), Some(new)) if old != new => diff.changed.push(KeyChange {
+                    key: key.clone(),
+                    old: old.clone(),
+                    new: new.clone(),
+                }),
+                (Some(old), None) => diff.removed.push((key.clone(), old.clone())),
+                (None, Some(new)) => diff.added.push((key.clone(), new.clone())),
+                _ => {}
+            }
+        }
+
+        let (old_pictures, new_pictures) = (old.picture_entries(), new.picture_entries());
+        diff.added_pictures = multiset_difference(new_pictures, old_pictures);
+        diff.removed_pictures = multiset_difference(old_pictures, new_pictures);
+        diff
+    }
+
+    /// Returns whether the two tags are equal, i.e. there are no differences at all.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.vendor.is_none()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.added_pictures.is_empty()
+            && self.removed_pictures.is_empty()
+    }
+
+    /// Decodes the pictures which only exist in the new tag, skipping pictures that are encoded
+    /// improperly.
+    #[must_use]
+    pub fn added_pictures(&self) -> Vec<Picture> {
+        decode_pictures(&self.added_pictures)
+    }
+
+    /// Decodes the pictures which only exist in the old tag, skipping pictures that are encoded
+    /// improperly.
+    #[must_use]
+    pub fn removed_pictures(&self) -> Vec<Picture> {
+        decode_pictures(&self.removed_pictures)
+    }
+}
+
+impl Display for TagDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((old, new)) = &self.vendor {
+            writeln!(f, "~ vendor: {old:?} -> {new:?}")?;
+        }
+        for (key, values) in &self.added {
+            for value in values {
+                writeln!(f, "+ {key}={value}")?;
+            }
+        }
+        for (key, values) in &self.removed {
+            for value in values {
+                writeln!(f, "- {key}={value}")?;
+            }
+        }
+        for change in &self.changed {
+            writeln!(f, "~ {}: {:?} -> {:?}", change.key, change.old, change.new)?;
+        }
+        for (sign, pictures) in [("+", self.added_pictures()), ("-", self.removed_pictures())] {
+            for picture in pictures {
+                writeln!(
+                    f,
+                    "{sign} picture: {:?} ({}, {} bytes)",
+                    picture.picture_type,
+                    picture.mime_type,
+                    picture.data.len()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the elements of `a` which are not in `b`, counting duplicates.
+fn multiset_difference(a: &[String], b: &[String]) -> Vec<String> {
+    let mut remaining: Vec<&String> = b.iter().collect();
+    a.iter()
+        .filter(|item| {
+            remaining
+                .iter()
+                .position(|other| other == item)
+                .map(|index| remaining.swap_remove(index))
+                .is_none()
+        })
+        .cloned()
+        .collect()
+}
+
+fn decode_pictures(entries: &[String]) -> Vec<Picture> {
+    entries
+        .iter()
+        .filter_map(|entry| Picture::from_base64(entry).ok())
+        .collect()
+}
@@ -0,0 +1,168 @@
+/// The names of the comment fields proposed by the Vorbis comment spec, in their conventional
+/// uppercase form, sorted alphabetically.
+///
+/// See <https://xiph.org/vorbis/doc/v-comment.html#fieldnames> for more information. Many other
+/// fields are in common use, which [`is_standard_key`] recognizes as well.
+pub const STANDARD_FIELDS: &[&str] = &[
+    "ALBUM",
+    "ARTIST",
+    "CONTACT",
+    "COPYRIGHT",
+    "DATE",
+    "DESCRIPTION",
+    "GENRE",
+    "ISRC",
+    "LICENSE",
+    "LOCATION",
+    "ORGANIZATION",
+    "PERFORMER",
+    "TITLE",
+    "TRACKNUMBER",
+    "VERSION",
+];
+
+/// The [`STANDARD_FIELDS`], along with other fields in common use (e.g. `ALBUMARTIST`,
+/// `TRACKTOTAL` or the replaygain fields), in their conventional uppercase form. Sorted by their
+/// lowercase form, which is how keys are stored in a tag.
+const KNOWN_FIELDS: &[&str] = &[
+    "ALBUM",
+    "ALBUMARTIST",
+    "ALBUMARTISTSORT",
+    "ALBUMSORT",
+    "ARRANGER",
+    "ARTIST",
+    "ARTISTSORT",
+    "BPM",
+    "CATALOGNUMBER",
+    "COMMENT",
+    "COMPILATION",
+    "COMPOSER",
+    "COMPOSERSORT",
+    "CONDUCTOR",
+    "CONTACT",
+    "COPYRIGHT",
+    "DATE",
+    "DESCRIPTION",
+    "DISCNUMBER",
+    "DISCSUBTITLE",
+    "DISCTOTAL",
+    "ENCODED_BY",
+    "ENCODEDBY",
+    "ENCODER",
+    "ENCODER_OPTIONS",
+    "ENSEMBLE",
+    "GENRE",
+    "GROUPING",
+    "ISRC",
+    "LABEL",
+    "LANGUAGE",
+    "LICENSE",
+    "LOCATION",
+    "LYRICIST",
+    "LYRICS",
+    "MEDIA",
+    "METADATA_BLOCK_PICTURE",
+    "MOOD",
+    "MOVEMENT",
+    "MOVEMENTNAME",
+    "MOVEMENTTOTAL",
+    "ORGANIZATION",
+    "ORIGINALDATE",
+    "PERFORMER",
+    "PRODUCER",
+    "PUBLISHER",
+    "R128_ALBUM_GAIN",
+    "R128_TRACK_GAIN",
+    "RELEASECOUNTRY",
+    "REMIXER",
+    "REPLAYGAIN_ALBUM_GAIN",
+    "REPLAYGAIN_ALBUM_PEAK",
+    "REPLAYGAIN_TRACK_GAIN",
+    "REPLAYGAIN_TRACK_PEAK",
+    "SUBTITLE",
+    "TITLE",
+    "TITLESORT",
+    "TOTALDISCS",
+    "TOTALTRACKS",
+    "TRACKNUMBER",
+    "TRACKTOTAL",
+    "VERSION",
+    "WEBSITE",
+    "WORK",
+];
+
+/// Returns whether the given key is one of the well-known comment field names, ignoring case.
+///
+/// This covers the fields proposed by the Vorbis comment spec (e.g. `TITLE`, `ARTIST` or
+/// `DATE`), along with other fields in common use (e.g. `ALBUMARTIST`, `TRACKTOTAL` or
+/// `REPLAYGAIN_TRACK_GAIN`).
+///
+/// Keys which are not well-known are perfectly valid, but may be typos (e.g. `ARIST`).
+#[must_use]
+pub fn is_standard_key(key: &str) -> bool {
+    find_known_field(key).is_some()
+}
+
+/// Returns the conventional display form of a comment key, e.g. `ALBUMARTIST` for
+/// `albumartist`.
+///
+/// This is useful for presenting keys, which are stored in lowercase, to the
+/// user. Keys which are not well-known field names (see [`is_standard_key`]) are returned as-is.
+#[must_use]
+pub fn display_key(key: &str) -> &str {
+    find_known_field(key).unwrap_or(key)
+}
+
+fn find_known_field(key: &str) -> Option<&'static str> {
+    let key = key.bytes().map(|byte| byte.to_ascii_lowercase());
+    KNOWN_FIELDS
+        .binary_search_by(|field| {
+            let field = field.bytes().map(|byte| byte.to_ascii_lowercase());
+            field.cmp(key.clone())
+        })
+        .ok()
+        .map(|index| KNOWN_FIELDS[index])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_fields_are_sorted() {
+        assert!(
+            KNOWN_FIELDS
+                .windows(2)
+                .all(|pair| pair[0].to_ascii_lowercase() < pair[1].to_ascii_lowercase())
+        );
+        assert!(
+            KNOWN_FIELDS
+                .iter()
+                .all(|field| field.bytes().all(|byte| !byte.is_ascii_lowercase()))
+        );
+    }
+
+    #[test]
+    fn standard_fields_are_sorted() {
+        assert!(STANDARD_FIELDS.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(STANDARD_FIELDS.iter().all(|field| is_standard_key(field)));
+    }
+
+    #[test]
+    fn classify_keys() {
+        assert!(is_standard_key("TITLE"));
+        assert!(is_standard_key("tracknumber"));
+        assert!(is_standard_key("Replaygain_Track_Gain"));
+        // a typo
+        assert!(!is_standard_key("ARIST"));
+        // a custom application key
+        assert!(!is_standard_key("MYAPP_RATING"));
+    }
+
+    #[test]
+    fn display_keys() {
+        assert_eq!(display_key("albumartist"), "ALBUMARTIST");
+        assert_eq!(display_key("Title"), "TITLE");
+        assert_eq!(display_key("mycustomkey"), "mycustomkey");
+    }
+}
@@ -0,0 +1,66 @@
+/// The difference between the reference loudness of replaygain and EBU R128 in dB.
+const REFERENCE_OFFSET_DB: f64 = 5.0;
+
+/// Converts a replaygain gain in dB to an R128 gain in Q7.8 format. The result is rounded to the
+/// nearest step and saturates at the bounds of `i16`.
+///
+/// The `REPLAYGAIN_TRACK_GAIN` and `REPLAYGAIN_ALBUM_GAIN` comments store a gain in dB as text
+/// (e.g. `-6.35 dB`), relative to a reference loudness of -18 LUFS. The `R128_TRACK_GAIN` and
+/// `R128_ALBUM_GAIN` comments, which are used by opus, store a gain in Q7.8 fixed point format
+/// (1/256 dB) as an integer, relative to a reference loudness of -23 LUFS. See
+/// <https://www.rfc-editor.org/rfc/rfc7845#section-5.2.1> for more information.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn replaygain_to_r128(gain_db: f64) -> i16 {
+    // `as` saturates, and maps NaN to 0
+    ((gain_db - REFERENCE_OFFSET_DB) * 256.0).round() as i16
+}
+
+/// Converts an R128 gain in Q7.8 format to a replaygain gain in dB. See
+/// [`replaygain_to_r128`] for more info.
+#[must_use]
+pub fn r128_to_replaygain(gain_q8: i16) -> f64 {
+    f64::from(gain_q8) / 256.0 + REFERENCE_OFFSET_DB
+}
+
+/// Parses a replaygain gain, e.g. `-6.35 dB`. The ` dB` suffix is optional.
+pub fn parse_replaygain(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let value = value
+        .len()
+        .checked_sub(2)
+        .filter(|&end| value.is_char_boundary(end) && value[end..].eq_ignore_ascii_case("db"))
+        .map_or(value, |end| value[..end].trim_end());
+    value.parse().ok().filter(|gain: &f64| gain.is_finite())
+}
+
+/// Formats a replaygain gain in the conventional form, e.g. `-6.35 dB`.
+pub fn format_replaygain(gain_db: f64) -> String {
+    format!("{gain_db:.2} dB")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_gains() {
+        assert_eq!(parse_replaygain("-6.35 dB"), Some(-6.35));
+        assert_eq!(parse_replaygain("+2.10 DB"), Some(2.1));
+        assert_eq!(parse_replaygain("-6.35dB"), Some(-6.35));
+        assert_eq!(parse_replaygain(" -6.35 "), Some(-6.35));
+        assert_eq!(parse_replaygain("loud"), None);
+        assert_eq!(parse_replaygain("inf dB"), None);
+        assert_eq!(format_replaygain(-6.35), "-6.35 dB");
+    }
+
+    #[test]
+    fn convert_gains() {
+        // -6.35 dB relative to -18 LUFS is -11.35 dB relative to -23 LUFS
+        assert_eq!(replaygain_to_r128(-6.35), -2906);
+        assert!((r128_to_replaygain(-2906) - -6.35).abs() < 1.0 / 256.0);
+        assert_eq!(replaygain_to_r128(5.0), 0);
+        assert!((r128_to_replaygain(0) - 5.0).abs() < 1.0 / 256.0);
+        assert_eq!(replaygain_to_r128(1000.0), i16::MAX);
+    }
+}
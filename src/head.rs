@@ -0,0 +1,272 @@
+use std::io::{Cursor, Read, Seek};
+
+use ogg::{OggReadError, PacketReader};
+
+use crate::{Error, Result, stream};
+
+/// The Opus identification header, which is the first packet of every Opus stream.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc7845#section-5.1> for more information.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct OpusHead {
+    /// The version of the Opus header. Must be 1 for streams conforming to RFC 7845.
+    pub version: u8,
+    /// The number of output channels.
+    pub channel_count: u8,
+    /// The number of samples (at 48 kHz) to discard from the decoder output when starting
+    /// playback.
+    pub pre_skip: u16,
+    /// The sample rate of the original input, before encoding. This is informational only.
+    pub input_sample_rate: u32,
+    /// The gain to apply to the decoded output, in Q7.8 dB format.
+    pub output_gain: i16,
+    /// The channel mapping family.
+    pub channel_mapping_family: u8,
+    /// The channel mapping table, which is present for every channel mapping family except 0.
+    pub channel_mapping: Option<ChannelMapping>,
+}
+
+/// The channel mapping table of an [`OpusHead`], describing how the decoded channels of a
+/// multichannel stream map to the output channels.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc7845#section-5.1.1> for more information.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ChannelMapping {
+    /// The number of Opus streams encoded in each packet.
+    pub stream_count: u8,
+    /// The number of those streams which are coupled (stereo).
+    pub coupled_count: u8,
+    /// The decoded channel each output channel is taken from, one entry per output channel.
+    pub mapping: Vec<u8>,
+}
+
+impl Default for OpusHead {
+    /// A stereo header with no pre-skip, an input sample rate of 48 kHz, and no output gain.
+    fn default() -> Self {
+        Self {
+            version: 1,
+            channel_count: 2,
+            pre_skip: 0,
+            input_sample_rate: 48000,
+            output_gain: 0,
+            channel_mapping_family: 0,
+            channel_mapping: None,
+        }
+    }
+}
+
+impl OpusHead {
+    /// Parses an identification header from the data of the first packet of an Opus stream.
+    /// # Errors
+    /// This function will error if the data doesn't start with the `OpusHead` magic signature,
+    /// or if it is shorter than mandated by the spec.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if !data.starts_with(b"OpusHead") {
+            return Err(Error::NotOpus);
+        }
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(8); // length of string "OpusHead"
+
+        let mut buffer = [0; 1];
+        cursor.read_exact(&mut buffer)?;
+        let version = buffer[0];
+        cursor.read_exact(&mut buffer)?;
+        let channel_count = buffer[0];
+
+        let mut buffer = [0; 2];
+        cursor.read_exact(&mut buffer)?;
+        let pre_skip = u16::from_le_bytes(buffer);
+
+        let mut buffer = [0; 4];
+        cursor.read_exact(&mut buffer)?;
+        let input_sample_rate = u32::from_le_bytes(buffer);
+
+        let mut buffer = [0; 2];
+        cursor.read_exact(&mut buffer)?;
+        let output_gain = i16::from_le_bytes(buffer);
+
+        let mut buffer = [0; 1];
+        cursor.read_exact(&mut buffer)?;
+        let channel_mapping_family = buffer[0];
+
+        let channel_mapping = if channel_mapping_family == 0 {
+            None
+        } else {
+            let mut buffer = [0; 2];
+            cursor.read_exact(&mut buffer)?;
+            let mut mapping = vec![0; usize::from(channel_count)];
+            cursor.read_exact(&mut mapping)?;
+            Some(ChannelMapping {
+                stream_count: buffer[0],
+                coupled_count: buffer[1],
+                mapping,
+            })
+        };
+
+        Ok(Self {
+            version,
+            channel_count,
+            pre_skip,
+            input_sample_rate,
+            output_gain,
+            channel_mapping_family,
+            channel_mapping,
+        })
+    }
+
+    /// Reads the identification header of the first opus stream of a reader. Like
+    /// [`Tag::read_from`](crate::Tag::read_from), this skips the beginning-of-stream packets of
+    /// other logical streams, such as an Ogg Skeleton stream.
+    /// # Errors
+    /// This function will error if the stream is empty, if the reader is not an opus stream, or
+    /// for the same reasons as [`from_bytes`](Self::from_bytes).
+    pub fn read_from<R: Read + Seek>(mut f_in: R) -> Result<Self> {
+        stream::skip_id3v2(&mut f_in)?;
+        let mut reader = PacketReader::new(f_in);
+        let first_packet = crate::read_opus_head_packet(&mut reader, None)?;
+        Self::from_bytes(&first_packet.data)
+    }
+
+    /// Encodes this header into the packet format described by the spec. The channel mapping
+    /// table is written if there is one, regardless of the channel mapping family.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(21 + usize::from(self.channel_count));
+        output.extend_from_slice(b"OpusHead");
+        output.push(self.version);
+        output.push(self.channel_count);
+        output.extend_from_slice(&self.pre_skip.to_le_bytes());
+        output.extend_from_slice(&self.input_sample_rate.to_le_bytes());
+        output.extend_from_slice(&self.output_gain.to_le_bytes());
+        output.push(self.channel_mapping_family);
+        if let Some(channel_mapping) = &self.channel_mapping {
+            output.push(channel_mapping.stream_count);
+            output.push(channel_mapping.coupled_count);
+            output.extend_from_slice(&channel_mapping.mapping);
+        }
+        output
+    }
+}
+
+/// Checks whether a reader contains an Ogg Opus stream, by looking for an identification header
+/// among the beginning-of-stream packets. Nothing past the identification header is read.
+///
+/// Returns false for ogg files containing other codecs (e.g. Vorbis), as well as for data which
+/// is not an ogg stream at all.
+/// # Errors
+/// This function will error if reading from the reader fails, or if the ogg stream is corrupt
+/// (e.g. a page fails its checksum).
+pub fn is_opus<R: Read + Seek>(mut f_in: R) -> Result<bool> {
+    stream::skip_id3v2(&mut f_in)?;
+    let mut reader = PacketReader::new(f_in);
+    loop {
+        let packet = match reader.read_packet() {
+            Ok(Some(packet)) => packet,
+            Ok(None) | Err(OggReadError::NoCapturePatternFound) => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+        // all beginning-of-stream pages precede the data pages
+        if !packet.first_in_stream() {
+            return Ok(false);
+        }
+        if packet.data.starts_with(b"OpusHead") {
+            return Ok(true);
+        }
+    }
+}
+
+/// Returns the number of 48 kHz samples encoded in an Opus audio packet, based on its TOC byte.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc6716#section-3.1> for more information.
+pub fn packet_samples(packet: &[u8]) -> u64 {
+    let Some(&toc) = packet.first() else {
+        return 0;
+    };
+    let config = toc >> 3;
+    let frame_samples = match config {
+        // SILK-only: 10, 20, 40 or 60 ms
+        0..=11 => [480, 960, 1920, 2880][usize::from(config % 4)],
+        // Hybrid: 10 or 20 ms
+        12..=15 => [480, 960][usize::from(config % 2)],
+        // CELT-only: 2.5, 5, 10 or 20 ms
+        _ => [120, 240, 480, 960][usize::from(config % 4)],
+    };
+    let frame_count = match toc & 0b11 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => packet.get(1).map_or(0, |count| count & 0b0011_1111),
+    };
+    frame_samples * u64::from(frame_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use ogg::{PacketWriteEndInfo, PacketWriter};
+
+    use super::*;
+    use crate::tests::{comment_header, stream_with_skeleton};
+
+    #[test]
+    fn channel_mapping_round_trip() {
+        let head = OpusHead::read_from(
+            File::open("testfiles/silence_cover.opus").expect("Failed to open testfile"),
+        )
+        .unwrap();
+        assert_eq!(head.channel_mapping, None);
+        assert_eq!(head.to_bytes().len(), 19);
+
+        // 5.1 surround: 4 streams, 2 of them coupled
+        let surround = OpusHead {
+            channel_count: 6,
+            channel_mapping_family: 1,
+            channel_mapping: Some(ChannelMapping {
+                stream_count: 4,
+                coupled_count: 2,
+                mapping: vec![0, 4, 1, 2, 3, 5],
+            }),
+            ..OpusHead::default()
+        };
+        let bytes = surround.to_bytes();
+        assert_eq!(bytes.len(), 19 + 2 + 6);
+        assert_eq!(OpusHead::from_bytes(&bytes).unwrap(), surround);
+
+        // the table is cut short
+        assert!(OpusHead::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn read_after_skeleton_stream() {
+        let data = stream_with_skeleton(comment_header("opusmeta", &[]));
+        assert_eq!(OpusHead::read_from(data).unwrap(), OpusHead::default());
+    }
+
+    #[test]
+    fn detect_opus() {
+        let file = File::open("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        assert!(is_opus(file).unwrap());
+    }
+
+    #[test]
+    fn detect_vorbis() {
+        let mut output = Cursor::new(vec![]);
+        let mut writer = PacketWriter::new(&mut output);
+        let identification = [b"\x01vorbis".as_slice(), &[0; 23]].concat();
+        writer
+            .write_packet(identification, 1, PacketWriteEndInfo::EndPage, 0)
+            .unwrap();
+        writer
+            .write_packet(b"\x03vorbis".to_vec(), 1, PacketWriteEndInfo::EndStream, 0)
+            .unwrap();
+        output.set_position(0);
+        assert!(!is_opus(output).unwrap());
+    }
+
+    #[test]
+    fn detect_non_ogg() {
+        let data = b"RIFF\x24\0\0\0WAVEfmt \x10\0\0\0 not an ogg stream at all";
+        assert!(!is_opus(Cursor::new(data)).unwrap());
+        assert!(!is_opus(Cursor::new([])).unwrap());
+    }
+}
@@ -1,12 +1,15 @@
 //! Module containing iterator types.
 
+use std::ops::Range;
+
 use crate::Picture;
 use crate::Result;
+use crate::comment_header;
 
-type CommentHashIter<'a> = std::collections::hash_map::Iter<'a, String, Vec<String>>;
+type CommentMapIter<'a> = std::slice::Iter<'a, (String, Vec<String>)>;
 
 type CommentsExceptPicturesIter<'a> =
-    std::iter::Filter<CommentHashIter<'a>, fn(&(&String, &Vec<String>)) -> bool>;
+    std::iter::Filter<CommentMapIter<'a>, fn(&&(String, Vec<String>)) -> bool>;
 
 /// An iterator over the comments of an opus file, excluding pictures.
 ///
@@ -44,3 +47,60 @@ impl Iterator for PicturesIterator<'_> {
         self.pictures_iter.next().map(|s| Picture::from_base64(s))
     }
 }
+
+/// An iterator over the byte ranges of the comments in a [`ParsedHeader`](crate::ParsedHeader).
+///
+/// The iterator Item is `(Range<usize>, Range<usize>)`, containing the ranges of the key and the
+/// value within the packet. For a comment without a `=` (see
+/// [`ReadOptions::allow_bare_keys`](crate::ReadOptions::allow_bare_keys)), the value range is
+/// empty.
+pub struct CommentRangesIterator<'a> {
+    pub(crate) packet: &'a [u8],
+    pub(crate) position: usize,
+    pub(crate) index: u32,
+    pub(crate) count: u32,
+}
+
+impl Iterator for CommentRangesIterator<'_> {
+    type Item = (Range<usize>, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.count {
+            return None;
+        }
+        let comment = comment_header::comment_at(self.packet, self.position, self.index).ok()?;
+        self.position = comment.end;
+        self.index += 1;
+
+        let separator = self.packet[comment.clone()]
+            .iter()
+            .position(|&byte| byte == b'=');
+        Some(separator.map_or_else(
+            || (comment.clone(), comment.end..comment.end),
+            |offset| {
+                let separator = comment.start + offset;
+                (comment.start..separator, separator + 1..comment.end)
+            },
+        ))
+    }
+}
+
+/// An iterator over the comments in a [`ParsedHeader`](crate::ParsedHeader).
+///
+/// The iterator Item is `(&'a str, &'a str)`, borrowing the key and the value from the packet.
+pub struct ParsedCommentsIterator<'a> {
+    pub(crate) ranges: CommentRangesIterator<'a>,
+}
+
+impl<'a> Iterator for ParsedCommentsIterator<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.ranges.next()?;
+        let packet = self.ranges.packet;
+        // the whole header was validated when it was parsed
+        let key = comment_header::to_str(&packet[key]).ok()?;
+        let value = comment_header::to_str(&packet[value]).ok()?;
+        Some((key, value))
+    }
+}
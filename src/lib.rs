@@ -1,22 +1,49 @@
 #![allow(clippy::module_name_repetitions)]
 #![doc = include_str!("../README.md")]
 
+mod comment_header;
+mod comment_map;
+mod comment_stream;
+mod diff;
+mod fields;
+mod gain;
+mod head;
 pub mod iter;
+mod options;
 pub mod picture;
+mod standard_tags;
+mod stream;
 mod utils;
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::fmt::Display;
+use std::collections::hash_map::RandomState;
+use std::fmt::{Debug, Display};
 use std::fs::File;
 use std::fs::OpenOptions;
+use std::hash::BuildHasher;
 use std::io::Cursor;
-use std::io::{Read, Seek, Write};
-use std::path::Path;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
+use comment_map::CommentMap;
 use iter::{CommentsIterator, PicturesIterator};
 use ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
 use picture::{Picture, PictureError, PictureType};
+use stream::PageHeader;
 
+pub use comment_header::{ParsedHeader, parse_comment_header};
+pub use comment_map::Entry;
+pub use comment_stream::CommentStream;
+pub use diff::{KeyChange, TagDiff};
+pub use fields::{STANDARD_FIELDS, display_key, is_standard_key};
+pub use gain::{r128_to_replaygain, replaygain_to_r128};
+pub use head::{ChannelMapping, OpusHead, is_opus};
+pub use options::{PicturePlacement, ReadOptions, WriteOptions};
+pub use standard_tags::StandardTags;
+pub use stream::StreamStats;
 pub use utils::LowercaseString;
 
 /// Error type.
@@ -50,6 +77,40 @@ pub enum Error {
     /// Raised if the platform's `usize` is smaller than 32 bits. This error is raised because
     /// the opus spec uses u32 for lengths, but Rust uses usize instead.
     PlatformError(std::num::TryFromIntError),
+    /// The comment header ended before the vendor string (or its length) was complete. This is
+    /// also returned if the declared vendor length exceeds the size of the header, before
+    /// anything is allocated for it.
+    TruncatedVendor,
+    /// The comment with the given (zero-based) index is not valid UTF-8. The key is provided if
+    /// it is valid UTF-8 itself, and the underlying `FromUtf8Error` provides the offending bytes.
+    CommentUTFError {
+        index: u32,
+        key: Option<String>,
+        error: std::string::FromUtf8Error,
+    },
+    /// The comment header ended before the comment count was complete.
+    TruncatedCommentCount,
+    /// The comment header ended before the comment with the given (zero-based) index (or its
+    /// length) was complete.
+    TruncatedComment { index: u32 },
+    /// No logical stream with the given serial number starts in the ogg stream.
+    SerialNotFound(u32),
+    /// The ogg page with the given (zero-based) index in the written stream is malformed, e.g.
+    /// because a packet spanning several pages was split incorrectly. The target is left
+    /// untouched when this is raised.
+    InvalidPage { page: usize },
+    /// The comment header contains more pictures than allowed by
+    /// [`ReadOptions::max_pictures`].
+    TooManyPictures { limit: usize },
+    /// A value of the comment with the given key contains a control character. Only raised if
+    /// [`WriteOptions::reject_control_chars`] is set.
+    ControlCharacter { key: String },
+    /// The data is a Matroska or `WebM` file (which can contain opus audio as well) rather than an
+    /// ogg file. Such files are not supported.
+    NotOggContainer,
+    /// An error occured while encoding an image with the `image` crate.
+    #[cfg(feature = "image")]
+    ImageError(image::ImageError),
 }
 
 impl Display for Error {
@@ -64,6 +125,18 @@ impl Display for Error {
             Self::TooBigError => f.write_str("The content was too big for the Opus spec"),
             Self::PictureError(err) => write!(f, "An error occured while encoding or decoding a picture: {err}"),
             Self::PlatformError(_) => f.write_str("This crate expects `usize` to be at least 32 bits in size."),
+            Self::TruncatedVendor => f.write_str("The comment header ended in the middle of the vendor string"),
+            Self::CommentUTFError { index, key: Some(key), .. } => write!(f, "Comment #{index} ({key}) is not valid UTF-8"),
+            Self::CommentUTFError { index, key: None, .. } => write!(f, "Comment #{index} is not valid UTF-8"),
+            Self::TruncatedCommentCount => f.write_str("The comment header ended in the middle of the comment count"),
+            Self::TruncatedComment { index } => write!(f, "The comment header ended in the middle of comment #{index}"),
+            Self::SerialNotFound(serial) => write!(f, "No logical stream with serial {serial} was found"),
+            Self::InvalidPage { page } => write!(f, "Page #{page} of the written stream is malformed"),
+            Self::TooManyPictures { limit } => write!(f, "The comment header contains more than {limit} pictures"),
+            Self::ControlCharacter { key } => write!(f, "A value of the comment {key} contains a control character"),
+            Self::NotOggContainer => f.write_str("The selected file is a Matroska/WebM file, not an ogg file"),
+            #[cfg(feature = "image")]
+            Self::ImageError(err) => write!(f, "An error occured while encoding an image: {err}"),
         }
     }
 }
@@ -76,6 +149,13 @@ impl From<std::num::TryFromIntError> for Error {
     }
 }
 
+#[cfg(feature = "image")]
+impl From<image::ImageError> for Error {
+    fn from(v: image::ImageError) -> Self {
+        Self::ImageError(v)
+    }
+}
+
 impl From<PictureError> for Error {
     fn from(v: PictureError) -> Self {
         Self::PictureError(v)
@@ -104,43 +184,169 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 const PICTURE_BLOCK_TAG: &str = "metadata_block_picture";
 
+/// The keys removed by [`Tag::strip_personal`].
+const PERSONAL_KEYS: &[&str] = &[
+    "comment",
+    "description",
+    "contact",
+    "location",
+    PICTURE_BLOCK_TAG,
+];
+
+/// The number of packets following the identification header which are searched for the comment
+/// header if [`ReadOptions::scan_for_tags`] is set.
+const TAG_SCAN_LIMIT: usize = 8;
+
+/// Pages written by [`Tag::build_stream`] are ended once they hold at least this many bytes.
+const TARGET_PAGE_SIZE: usize = 4096;
+
+/// The ways in which track and disc numbers can be stored. See
+/// [`Tag::normalize_track_fields`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TrackStyle {
+    /// The number and the total in one comment, e.g. `TRACKNUMBER=3/12`.
+    Combined,
+    /// The number and the total in separate comments, e.g. `TRACKNUMBER=3` and `TRACKTOTAL=12`.
+    Split,
+}
+
 /// Stores Opus comments.
-#[derive(Debug, Default)]
+///
+/// Two tags are equal if they have the same vendor string and the same values for every key. The
+/// order of the keys doesn't matter, but the order of the values within a key does.
+///
+/// A tag remembers the order and the original case of the comments it was created from, and any
+/// data following the comments in the comment header (such as padding). Writing an unmodified tag
+/// reproduces the original comment header exactly.
+///
+/// The `Debug` output abbreviates the pictures and the data following the comments, to keep
+/// logs readable. Use [`picture_entries`](Self::picture_entries) to get the full picture data.
+#[derive(Default, Clone)]
 pub struct Tag {
     vendor: String,
-    comments: HashMap<String, Vec<String>>,
+    comments: CommentMap,
+    /// The keys of the comments the tag was created from, in their original order and case.
+    layout: Vec<String>,
+    /// The data following the comments in the comment header the tag was read from.
+    trailing_data: Vec<u8>,
+    /// Whether merging the comments the tag was created from by key lost their original layout
+    /// (see [`Tag::had_duplicate_keys`]).
+    had_duplicate_keys: bool,
+}
+
+impl PartialEq for Tag {
+    fn eq(&self, other: &Self) -> bool {
+        self.vendor == other.vendor && self.comments == other.comments
+    }
+}
+
+impl Eq for Tag {}
+
+impl Debug for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /// Prints the start and the length of a base64 encoded picture.
+        struct Abbreviated<'a>(&'a str);
+
+        impl Debug for Abbreviated<'_> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let start = self.0.get(..16).unwrap_or(self.0);
+                write!(f, "\"{start}...\" [{} bytes]", self.0.len())
+            }
+        }
+
+        struct Comments<'a>(&'a CommentMap);
+
+        impl Debug for Comments<'_> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let mut map = f.debug_map();
+                for (key, values) in self.0 {
+                    if key == PICTURE_BLOCK_TAG {
+                        let pictures: Vec<_> =
+                            values.iter().map(|value| Abbreviated(value)).collect();
+                        map.entry(key, &pictures);
+                    } else {
+                        map.entry(key, values);
+                    }
+                }
+                map.finish()
+            }
+        }
+
+        f.debug_struct("Tag")
+            .field("vendor", &self.vendor)
+            .field("comments", &Comments(&self.comments))
+            .field("layout", &self.layout)
+            .field(
+                "trailing_data",
+                &format_args!("[{} bytes]", self.trailing_data.len()),
+            )
+            .field("had_duplicate_keys", &self.had_duplicate_keys)
+            .finish()
+    }
 }
 
 impl Tag {
     /// Create a new tag from a vendor string and a list of comments.
+    ///
+    /// The order and the case of the keys are remembered, and the comments are written in the
+    /// same order and case as far as they still exist. Comments added later are written after
+    /// them, with lowercase keys.
     #[must_use]
     pub fn new(vendor: String, comments: Vec<(String, String)>) -> Self {
-        let mut comments_map = HashMap::new();
-        for (mut key, value) in comments {
-            key.make_ascii_lowercase();
-            comments_map.entry(key).or_insert_with(Vec::new).push(value);
-        }
-
-        Self {
+        let mut tag = Self {
             vendor,
-            comments: comments_map,
-        }
+            layout: comments.iter().map(|(key, _)| key.clone()).collect(),
+            ..Self::default()
+        };
+        tag.extend(comments);
+        tag.had_duplicate_keys = merges_duplicate_keys(&tag.layout);
+        tag
+    }
+
+    /// Returns whether merging the comments this tag was created from (e.g. by
+    /// [`read_from`](Self::read_from) or [`new`](Self::new)) into one list of values per key lost
+    /// information. This is the case if a key occurred more than once with a different case (e.g.
+    /// `Artist` and `ARTIST`), or with other keys in between, since writing the tag groups the
+    /// values of a key under a single spelling. Pictures are not taken into account.
+    ///
+    /// Several values for the same key in a row (e.g. one `ARTIST` comment per artist) are
+    /// allowed by the spec and don't set this. Changes made after the tag was created don't
+    /// affect this.
+    #[must_use]
+    pub const fn had_duplicate_keys(&self) -> bool {
+        self.had_duplicate_keys
     }
 
     /// Add one entry.
     pub fn add_one(&mut self, tag: LowercaseString, value: String) {
-        self.comments
-            .entry(tag.0.into_owned())
-            .or_default()
-            .push(value);
+        self.comments.get_or_default(tag.0.into_owned()).push(value);
+    }
+
+    /// Add one entry, but only if there are no entries for the key yet. Returns whether the entry
+    /// was added. This is useful for filling in fallback values without overwriting existing ones.
+    pub fn add_if_absent(&mut self, tag: LowercaseString, value: String) -> bool {
+        if self
+            .comments
+            .get(tag.0.as_ref())
+            .is_some_and(|v| !v.is_empty())
+        {
+            return false;
+        }
+        self.add_one(tag, value);
+        true
     }
 
     /// Add multiple entries.
     pub fn add_many(&mut self, tag: LowercaseString, mut values: Vec<String>) {
         self.comments
-            .entry(tag.0.into_owned())
-            .and_modify(|v: &mut Vec<String>| v.append(&mut values))
-            .or_insert(values);
+            .get_or_default(tag.0.into_owned())
+            .append(&mut values);
+    }
+
+    /// Gets the entry of a key for in-place manipulation, similar to
+    /// [`HashMap::entry`](std::collections::HashMap::entry).
+    pub fn entry(&mut self, tag: LowercaseString) -> Entry<'_> {
+        self.comments.entry(tag.0.into_owned())
     }
 
     /// Get all entries for a particular key, or None if no occurrences of the key exist.
@@ -155,11 +361,41 @@ impl Tag {
         self.comments.get(tag.0.as_ref()).and_then(|v| v.first())
     }
 
+    /// Gets the first entry for a particular key, parsed into the requested type (e.g. a number
+    /// for `TRACKNUMBER` or `BPM`). Returns None if no occurrences of the key exist, and the parse
+    /// error if the entry can't be parsed.
+    ///
+    /// The entry is parsed as-is, so a combined track number like `3/12` doesn't parse as an
+    /// integer. See [`normalize_track_fields`](Self::normalize_track_fields) for splitting those.
+    #[must_use]
+    pub fn get_one_parsed<T: FromStr>(
+        &self,
+        tag: &LowercaseString,
+    ) -> Option<std::result::Result<T, T::Err>> {
+        self.get_one(tag).map(|value| value.parse())
+    }
+
     /// Remove all entries for a particular key. Optionally returns the removed values, if any.
     pub fn remove_entries(&mut self, tag: &LowercaseString) -> Option<Vec<String>> {
         self.comments.remove(tag.0.as_ref())
     }
 
+    /// Moves the value at index `from` of a key to index `to`, shifting the values in between.
+    /// This is useful for keys where the order of the values matters, e.g. to make an artist
+    /// the primary (first) artist. Returns false without changing anything if the key doesn't
+    /// exist or either index is out of range.
+    pub fn move_value(&mut self, tag: &LowercaseString, from: usize, to: usize) -> bool {
+        let Some(values) = self.comments.get_mut(tag) else {
+            return false;
+        };
+        if from >= values.len() || to >= values.len() {
+            return false;
+        }
+        let value = values.remove(from);
+        values.insert(to, value);
+        true
+    }
+
     /// Remove all entries for a particular key, inserting the given values instead.
     pub fn set_entries(
         &mut self,
@@ -169,6 +405,438 @@ impl Tag {
         self.comments.insert(tag.0.into_owned(), values)
     }
 
+    /// Sets several common fields at once. Fields which are `None` are left untouched, and fields
+    /// which are `Some` replace all existing entries of their key, like
+    /// [`set_entries`](Self::set_entries).
+    pub fn apply_standard(&mut self, tags: &StandardTags) {
+        let fields = [
+            ("title", tags.title.clone()),
+            ("artist", tags.artist.clone()),
+            ("album", tags.album.clone()),
+            ("date", tags.date.clone()),
+            ("tracknumber", tags.track.map(|track| track.to_string())),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                self.set_entries(LowercaseString::from_str(key), vec![value]);
+            }
+        }
+    }
+
+    /// Replaces all comments with the given map. Keys are lowercased, and the values of keys which
+    /// only differ in case are merged, ordered by the original keys. The pictures are kept, unless
+    /// the map contains a picture block of its own.
+    ///
+    /// The order and case of the comments the tag was created from are forgotten, so the new
+    /// comments are written sorted by key, with lowercase keys, followed by the kept pictures.
+    pub fn set_comments(&mut self, comments: HashMap<String, Vec<String>>) {
+        let pictures = self.comments.remove(PICTURE_BLOCK_TAG);
+        self.comments.clear();
+        self.layout.clear();
+
+        let mut comments: Vec<(String, Vec<String>)> = comments.into_iter().collect();
+        comments.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        for (key, values) in comments {
+            self.add_many(LowercaseString::from_string(key), values);
+        }
+
+        if let Some(pictures) = pictures
+            && !self.comments.contains_key(PICTURE_BLOCK_TAG)
+        {
+            self.comments.insert(PICTURE_BLOCK_TAG.into(), pictures);
+        }
+    }
+
+    /// Gets all entries for a particular key, splitting every entry on the given separator. This
+    /// is useful for files which pack multiple values into one comment (e.g. `ARTIST=A;B`) instead
+    /// of repeating the key. Returns an empty Vec if no occurrences of the key exist.
+    #[must_use]
+    pub fn get_split(&self, tag: &LowercaseString, sep: char) -> Vec<String> {
+        self.get(tag).map_or_else(Vec::new, |values| {
+            values
+                .iter()
+                .flat_map(|value| value.split(sep))
+                .map(ToString::to_string)
+                .collect()
+        })
+    }
+
+    /// Remove all entries for a particular key, inserting the given values joined by the
+    /// given separator as a single entry instead. This is the inverse of
+    /// [`get_split`](Self::get_split).
+    pub fn set_joined(
+        &mut self,
+        tag: LowercaseString,
+        values: &[String],
+        sep: char,
+    ) -> Option<Vec<String>> {
+        let joined = values.join(sep.encode_utf8(&mut [0; 4]));
+        self.set_entries(tag, vec![joined])
+    }
+
+    /// Keep only the entries for which the predicate returns true. The predicate is called with
+    /// the key and the value of every single entry, and keys which are left without any values
+    /// are removed entirely.
+    ///
+    /// Pictures are not passed to the predicate and are always kept. Use
+    /// [`remove_picture_type`](Self::remove_picture_type) to remove pictures.
+    pub fn retain(&mut self, mut f: impl FnMut(&str, &str) -> bool) {
+        self.comments.retain(|key, values| {
+            if key == PICTURE_BLOCK_TAG {
+                return true;
+            }
+            values.retain(|value| f(key, value));
+            !values.is_empty()
+        });
+    }
+
+    /// Removes keys without any values, which can be left behind when building a tag
+    /// incrementally (e.g. with [`entry`](Self::entry)). If `drop_empty_values` is set, empty
+    /// values are removed first, so keys whose values are all empty are removed as well.
+    pub fn prune_empty(&mut self, drop_empty_values: bool) {
+        self.comments.retain(|_, values| {
+            if drop_empty_values {
+                values.retain(|value| !value.is_empty());
+            }
+            !values.is_empty()
+        });
+    }
+
+    /// Truncates every comment value which is longer than `max_bytes` bytes. Values are cut at the
+    /// last UTF-8 character boundary within the limit, so they may end up slightly shorter.
+    /// Returns the number of values which were truncated.
+    ///
+    /// Pictures are not truncated, since that would corrupt them.
+    pub fn truncate_values(&mut self, max_bytes: usize) -> usize {
+        let mut truncated = 0;
+        self.comments.retain(|key, values| {
+            if key == PICTURE_BLOCK_TAG {
+                return true;
+            }
+            for value in values.iter_mut() {
+                if utils::truncate_at_char_boundary(value, max_bytes) {
+                    truncated += 1;
+                }
+            }
+            true
+        });
+        truncated
+    }
+
+    /// Removes comments which may identify the person who created or tagged the file, such as
+    /// personal notes or contact details. These are the `COMMENT`, `DESCRIPTION`, `CONTACT` and
+    /// `LOCATION` fields, and all pictures. Descriptive fields such as `TITLE`, `ARTIST` and
+    /// `ALBUM` are kept.
+    ///
+    /// Use [`strip_keys`](Self::strip_keys) to remove a different set of fields.
+    pub fn strip_personal(&mut self) {
+        self.strip_keys(PERSONAL_KEYS);
+    }
+
+    /// Removes all values of the given keys, ignoring case. Pass `METADATA_BLOCK_PICTURE` to
+    /// remove all pictures as well.
+    pub fn strip_keys(&mut self, keys: &[&str]) {
+        for key in keys {
+            self.remove_entries(&LowercaseString::from_str(key));
+        }
+    }
+
+    /// Writes every picture to a file in the given directory, which is created if it doesn't
+    /// exist. The files are named after the picture type, with an extension guessed from the
+    /// picture data or its MIME type (e.g. `cover_front.jpg`). If several pictures share a type,
+    /// a number is appended to the names of all but the first (e.g. `other_2.png`). Existing
+    /// files are overwritten.
+    ///
+    /// Pictures which fail to decode are skipped. Returns the paths of the created files, in the
+    /// order of the pictures.
+    /// # Errors
+    /// This function will return an error if the directory can't be created, or if a file can't
+    /// be written.
+    pub fn extract_pictures_to_dir<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for picture in self.pictures_iter().filter_map(Result::ok) {
+            let stem = picture.picture_type.file_stem();
+            let extension = picture.file_extension();
+            let mut path = dir.join(format!("{stem}.{extension}"));
+            let mut number = 2;
+            while paths.contains(&path) {
+                path = dir.join(format!("{stem}_{number}.{extension}"));
+                number += 1;
+            }
+            std::fs::write(&path, &picture.data)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Reorders the keys using a comparator, which is called with the lowercase keys (including
+    /// `metadata_block_picture` for the pictures). The values of every key keep their order.
+    /// Subsequent writes encode the comments in the new order, unless
+    /// [`WriteOptions::sort`] is set.
+    pub fn sort_keys_by(&mut self, cmp: impl FnMut(&str, &str) -> Ordering) {
+        self.comments.sort_by(cmp);
+
+        // the layout decides the order of the written comments, so it is rebuilt in the new
+        // order, keeping the original case of the keys and adding slots for values added later
+        let mut slots: HashMap<String, Vec<String>> = HashMap::new();
+        for key in self.layout.drain(..) {
+            slots.entry(key.to_ascii_lowercase()).or_default().push(key);
+        }
+        for (key, values) in &self.comments {
+            let mut keys = slots.remove(key).unwrap_or_default();
+            if keys.len() < values.len() {
+                keys.resize(values.len(), key.clone());
+            }
+            self.layout.extend(keys);
+        }
+    }
+
+    /// Gets all artists (`ARTIST` entries). Returns an empty slice if there are none.
+    #[must_use]
+    pub fn artists(&self) -> &[String] {
+        self.values("artist")
+    }
+
+    /// Adds an artist (an `ARTIST` entry), after any existing ones.
+    pub fn add_artist(&mut self, artist: String) {
+        self.add_one(LowercaseString::from_str("artist"), artist);
+    }
+
+    /// Gets all genres (`GENRE` entries). Returns an empty slice if there are none.
+    #[must_use]
+    pub fn genres(&self) -> &[String] {
+        self.values("genre")
+    }
+
+    /// Adds a genre (a `GENRE` entry), after any existing ones.
+    pub fn add_genre(&mut self, genre: String) {
+        self.add_one(LowercaseString::from_str("genre"), genre);
+    }
+
+    /// Gets all performers (`PERFORMER` entries). Returns an empty slice if there are none.
+    #[must_use]
+    pub fn performers(&self) -> &[String] {
+        self.values("performer")
+    }
+
+    /// Adds a performer (a `PERFORMER` entry), after any existing ones.
+    pub fn add_performer(&mut self, performer: String) {
+        self.add_one(LowercaseString::from_str("performer"), performer);
+    }
+
+    /// Gets the first `ENCODER` entry, or None if there is none.
+    ///
+    /// Note that this is distinct from the [vendor string](Self::get_vendor): the vendor string
+    /// is part of the comment header itself and identifies the library which wrote the header
+    /// (e.g. `libopus 1.4` or `Lavf61.7.100`), while the `ENCODER` comment is an ordinary comment
+    /// which tools like opusenc use to record the application and settings used for encoding.
+    #[must_use]
+    pub fn encoder(&self) -> Option<&str> {
+        self.values("encoder").first().map(String::as_str)
+    }
+
+    /// Sets the `ENCODER` entry, replacing any existing ones. See [`encoder`](Self::encoder) for
+    /// how this differs from the vendor string.
+    pub fn set_encoder(&mut self, encoder: &str) {
+        self.set_entries(LowercaseString::from_str("encoder"), vec![encoder.into()]);
+    }
+
+    /// Gets the first `WORK` entry (the name of the work a track belongs to, e.g. a symphony), or
+    /// None if there is none.
+    #[must_use]
+    pub fn work(&self) -> Option<&str> {
+        self.values("work").first().map(String::as_str)
+    }
+
+    /// Sets the `WORK` entry, replacing any existing ones.
+    pub fn set_work(&mut self, work: &str) {
+        self.set_entries(LowercaseString::from_str("work"), vec![work.into()]);
+    }
+
+    /// Gets the first `MOVEMENT` entry (the number of the movement within the work), or None if
+    /// there is none or it is not a number.
+    #[must_use]
+    pub fn movement(&self) -> Option<u32> {
+        self.values("movement").first()?.trim().parse().ok()
+    }
+
+    /// Sets the `MOVEMENT` entry, replacing any existing ones.
+    pub fn set_movement(&mut self, movement: u32) {
+        self.set_entries(
+            LowercaseString::from_str("movement"),
+            vec![movement.to_string()],
+        );
+    }
+
+    /// Gets the first `MOVEMENTNAME` entry, or None if there is none.
+    #[must_use]
+    pub fn movement_name(&self) -> Option<&str> {
+        self.values("movementname").first().map(String::as_str)
+    }
+
+    /// Sets the `MOVEMENTNAME` entry, replacing any existing ones.
+    pub fn set_movement_name(&mut self, name: &str) {
+        self.set_entries(LowercaseString::from_str("movementname"), vec![name.into()]);
+    }
+
+    /// Gets the first `MOVEMENTTOTAL` entry (the number of movements in the work), or None if
+    /// there is none or it is not a number.
+    #[must_use]
+    pub fn movement_total(&self) -> Option<u32> {
+        self.values("movementtotal").first()?.trim().parse().ok()
+    }
+
+    /// Sets the `MOVEMENTTOTAL` entry, replacing any existing ones.
+    pub fn set_movement_total(&mut self, total: u32) {
+        self.set_entries(
+            LowercaseString::from_str("movementtotal"),
+            vec![total.to_string()],
+        );
+    }
+
+    /// Gets the first `REPLAYGAIN_TRACK_GAIN` entry in dB (e.g. `-6.35 dB`, the ` dB` suffix is
+    /// optional), or None if there is none or it can't be parsed.
+    ///
+    /// Opus players usually prefer [`r128_track_gain`](Self::r128_track_gain). See
+    /// [`replaygain_to_r128`] for converting between the two.
+    #[must_use]
+    pub fn replaygain_track_gain(&self) -> Option<f64> {
+        gain::parse_replaygain(self.values("replaygain_track_gain").first()?)
+    }
+
+    /// Sets the `REPLAYGAIN_TRACK_GAIN` entry in dB, replacing any existing ones. The gain is
+    /// written with two decimals, e.g. `-6.35 dB`.
+    pub fn set_replaygain_track_gain(&mut self, gain_db: f64) {
+        self.set_entries(
+            LowercaseString::from_str("replaygain_track_gain"),
+            vec![gain::format_replaygain(gain_db)],
+        );
+    }
+
+    /// Gets the first `REPLAYGAIN_ALBUM_GAIN` entry in dB. See
+    /// [`replaygain_track_gain`](Self::replaygain_track_gain) for more info.
+    #[must_use]
+    pub fn replaygain_album_gain(&self) -> Option<f64> {
+        gain::parse_replaygain(self.values("replaygain_album_gain").first()?)
+    }
+
+    /// Sets the `REPLAYGAIN_ALBUM_GAIN` entry in dB, replacing any existing ones. See
+    /// [`set_replaygain_track_gain`](Self::set_replaygain_track_gain) for more info.
+    pub fn set_replaygain_album_gain(&mut self, gain_db: f64) {
+        self.set_entries(
+            LowercaseString::from_str("replaygain_album_gain"),
+            vec![gain::format_replaygain(gain_db)],
+        );
+    }
+
+    /// Gets the first `R128_TRACK_GAIN` entry, a gain in Q7.8 format relative to -23 LUFS, or
+    /// None if there is none or it is not an integer.
+    #[must_use]
+    pub fn r128_track_gain(&self) -> Option<i16> {
+        self.values("r128_track_gain").first()?.trim().parse().ok()
+    }
+
+    /// Sets the `R128_TRACK_GAIN` entry, replacing any existing ones.
+    pub fn set_r128_track_gain(&mut self, gain_q8: i16) {
+        self.set_entries(
+            LowercaseString::from_str("r128_track_gain"),
+            vec![gain_q8.to_string()],
+        );
+    }
+
+    /// Gets the first `R128_ALBUM_GAIN` entry. See [`r128_track_gain`](Self::r128_track_gain)
+    /// for more info.
+    #[must_use]
+    pub fn r128_album_gain(&self) -> Option<i16> {
+        self.values("r128_album_gain").first()?.trim().parse().ok()
+    }
+
+    /// Sets the `R128_ALBUM_GAIN` entry, replacing any existing ones.
+    pub fn set_r128_album_gain(&mut self, gain_q8: i16) {
+        self.set_entries(
+            LowercaseString::from_str("r128_album_gain"),
+            vec![gain_q8.to_string()],
+        );
+    }
+
+    /// Gets the first `GROUPING` entry, or None if there is none.
+    #[must_use]
+    pub fn grouping(&self) -> Option<&str> {
+        self.values("grouping").first().map(String::as_str)
+    }
+
+    /// Sets the `GROUPING` entry, replacing any existing ones.
+    pub fn set_grouping(&mut self, grouping: &str) {
+        self.set_entries(LowercaseString::from_str("grouping"), vec![grouping.into()]);
+    }
+
+    /// Converts the `TRACKNUMBER`/`TRACKTOTAL` and `DISCNUMBER`/`DISCTOTAL` entries to the given
+    /// style in place. Only the first `TRACKNUMBER` (or `DISCNUMBER`) entry is converted.
+    ///
+    /// Entries are left untouched if they are not numeric, or if there is nothing to convert
+    /// (e.g. there is no total to combine with the number). When splitting, an existing total is
+    /// kept rather than overwritten.
+    pub fn normalize_track_fields(&mut self, style: TrackStyle) {
+        let is_numeric =
+            |value: &str| !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit());
+
+        for prefix in ["track", "disc"] {
+            let number_key = format!("{prefix}number");
+            let total_key = format!("{prefix}total");
+            let Some(number) = self.values(&number_key).first() else {
+                continue;
+            };
+
+            match style {
+                TrackStyle::Combined => {
+                    let (number, Some(total)) = (number.trim(), self.values(&total_key).first())
+                    else {
+                        continue;
+                    };
+                    let total = total.trim();
+                    if !is_numeric(number) || !is_numeric(total) {
+                        continue;
+                    }
+                    let combined = format!("{number}/{total}");
+                    self.set_first(&number_key, combined);
+                    self.comments.remove(&total_key);
+                }
+                TrackStyle::Split => {
+                    let Some((number, total)) = number.split_once('/') else {
+                        continue;
+                    };
+                    let (number, total) = (number.trim(), total.trim());
+                    if !is_numeric(number) || !(total.is_empty() || is_numeric(total)) {
+                        continue;
+                    }
+                    let (number, total) = (number.to_string(), total.to_string());
+                    self.set_first(&number_key, number);
+                    if !total.is_empty() && !self.comments.contains_key(&total_key) {
+                        self.comments.insert(total_key, vec![total]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replaces the first entry of a key, if there is one.
+    fn set_first(&mut self, key: &str, value: String) {
+        if let Some(first) = self
+            .comments
+            .get_mut(key)
+            .and_then(|values| values.first_mut())
+        {
+            *first = value;
+        }
+    }
+
+    fn values(&self, key: &str) -> &[String] {
+        self.comments.get(key).map_or(&[], Vec::as_slice)
+    }
+
     /// Gets the vendor string
     #[must_use]
     pub fn get_vendor(&self) -> &str {
@@ -180,6 +848,15 @@ impl Tag {
         self.vendor = new_vendor;
     }
 
+    /// Gets the data following the comments in the comment header the tag was read from, which
+    /// is empty for most files. Per the spec, this is padding if the lowest bit of its first byte
+    /// is clear, and binary data to be preserved otherwise. It is written back unchanged by
+    /// [`write_to`](Self::write_to).
+    #[must_use]
+    pub fn trailing_data(&self) -> &[u8] {
+        &self.trailing_data
+    }
+
     /// Add a picture. If a picture with the same `PictureType` already exists, it is removed first.
     /// # Errors
     /// This function will error  if encoding the given data to Opus format or to base64 errors.
@@ -190,6 +867,44 @@ impl Tag {
         Ok(())
     }
 
+    /// Add a picture, filling in its width and height from the picture data. If a picture with the
+    /// same `PictureType` already exists, it is removed first.
+    ///
+    /// This is useful for pictures created with [`Picture::read_from`], which leaves the
+    /// dimensions as 0, since some players rely on them. See [`Picture::decode_dimensions`] for
+    /// the supported formats. The dimensions of pictures in other formats are left as they are.
+    /// # Errors
+    /// This function will error for the same reasons as [`add_picture`](Self::add_picture).
+    pub fn add_picture_with_dimensions(&mut self, picture: &Picture) -> Result<()> {
+        let Some((width, height)) = picture.decode_dimensions() else {
+            return self.add_picture(picture);
+        };
+        self.add_picture(&Picture {
+            width,
+            height,
+            ..picture.clone()
+        })
+    }
+
+    /// Add a picture, after checking that it is sensible. If a picture with the same `PictureType`
+    /// already exists, it is removed first.
+    ///
+    /// Unlike [`add_picture`](Self::add_picture), this rejects pictures with an empty MIME type or
+    /// empty data.
+    /// # Errors
+    /// This function will error with [`PictureError::EmptyMimeType`] or
+    /// [`PictureError::EmptyData`] if the picture fails the checks, and for the same reasons as
+    /// [`add_picture`](Self::add_picture).
+    pub fn add_picture_checked(&mut self, picture: &Picture) -> Result<()> {
+        if picture.mime_type.is_empty() {
+            return Err(PictureError::EmptyMimeType.into());
+        }
+        if picture.data.is_empty() {
+            return Err(PictureError::EmptyData.into());
+        }
+        self.add_picture(picture)
+    }
+
     /// Removes a picture with the given picture type. Returns the removed picture for convenience.
     /// # Errors
     /// This function will never error.
@@ -211,6 +926,49 @@ impl Tag {
         Ok(None)
     }
 
+    /// Removes the picture at the given index, counting from zero in the order returned by
+    /// [`picture_entries`](Self::picture_entries). The pictures after it move down by one index.
+    /// Returns the decoded form of the removed picture, or None if the index is out of range.
+    ///
+    /// The picture is removed even if it fails to decode.
+    pub fn remove_picture_at(&mut self, index: usize) -> Option<Result<Picture>> {
+        let pictures = self.comments.get_mut(PICTURE_BLOCK_TAG)?;
+        if index >= pictures.len() {
+            return None;
+        }
+        let data = pictures.remove(index);
+        if pictures.is_empty() {
+            self.comments.remove(PICTURE_BLOCK_TAG);
+        }
+        Some(Picture::from_base64(&data))
+    }
+
+    /// Removes all pictures except the front cover, or except the first picture if there is no
+    /// front cover, for players which only read the first picture. If there are several front
+    /// covers, the first one is kept.
+    /// # Errors
+    /// This function will error if a picture before the first front cover fails to decode, since
+    /// its picture type can't be determined. In that case, no pictures are removed.
+    pub fn keep_only_front_cover(&mut self) -> Result<()> {
+        let Some(pictures) = self.comments.get_mut(PICTURE_BLOCK_TAG) else {
+            return Ok(());
+        };
+        if pictures.is_empty() {
+            return Ok(());
+        }
+
+        let mut front_cover = None;
+        for (index, data) in pictures.iter().enumerate() {
+            if Picture::from_base64(data)?.picture_type == PictureType::CoverFront {
+                front_cover = Some(index);
+                break;
+            }
+        }
+        let kept = pictures.swap_remove(front_cover.unwrap_or(0));
+        *pictures = vec![kept];
+        Ok(())
+    }
+
     /// Gets a picture which has a certain picture type, or None if there are no pictures with that
     /// type.
     #[must_use]
@@ -233,126 +991,436 @@ impl Tag {
         self.comments.contains_key(PICTURE_BLOCK_TAG)
     }
 
+    /// Returns the raw, base64-encoded picture entries, without decoding them. Returns an empty
+    /// slice if there are no pictures.
+    #[must_use]
+    pub fn picture_entries(&self) -> &[String] {
+        self.values(PICTURE_BLOCK_TAG)
+    }
+
     /// Returns a Vec of all encoded pictures. This function will skip pictures that are encoded
     /// improperly.
     #[must_use]
     pub fn pictures(&self) -> Vec<Picture> {
-        self.iter_pictures()
-            .map_or_else(Vec::new, |iter| iter.filter_map(Result::ok).collect())
+        self.pictures_iter().filter_map(Result::ok).collect()
+    }
+
+    /// Like [`pictures`](Self::pictures), but pictures whose MIME type or description is not
+    /// valid UTF-8 are still returned, with the invalid bytes replaced. See
+    /// [`Picture::from_bytes_lossy`] for more info.
+    #[must_use]
+    pub fn pictures_lossy(&self) -> Vec<Picture> {
+        self.picture_entries()
+            .iter()
+            .filter_map(|entry| Picture::from_base64_lossy(entry).ok())
+            .collect()
     }
 }
 
 impl Tag {
     /// Read a `Tag` from a reader.
+    ///
+    /// An `ID3v2` tag in front of the ogg stream, which some tools prepend, is skipped.
+    ///
+    /// Reading stops at the end of the comment header, so a file which is cut off after it (e.g.
+    /// because only its start was fetched) can still be read. See
+    /// [`read_header_only`](Self::read_header_only) for more info.
     /// # Errors
     /// This function can error if:
     /// - The ogg stream is shorter than expected (e.g. doesn't include the first or second packets)
-    /// - The given reader is not an opus stream
+    /// - The given reader is not an opus stream, or is a Matroska/`WebM` file
     /// - The comment header does not include the magic signature
     /// - The comment header is shorter than mandated by the spec
     /// - The platform's usize is not at least 32 bits long
     /// - The spec mandates UTF-8, but the data is invalid unicode
     /// - A comment line is not in TAG=VALUE format.
     pub fn read_from<R: Read + Seek>(f_in: R) -> Result<Self> {
-        let mut reader = PacketReader::new(f_in);
-        let first_packet = reader.read_packet()?.ok_or(Error::MissingPacket)?;
-        if !first_packet.data.starts_with(b"OpusHead") {
-            return Err(Error::NotOpus);
-        }
-        let header_packet = reader.read_packet()?.ok_or(Error::MissingPacket)?;
-        let mut cursor = Cursor::new(header_packet.data);
-        cursor.seek_relative(8)?; // length of string "OpusTags"
-        let mut buffer = [0; 4];
-        cursor.read_exact(&mut buffer)?;
-        // only panics on platforms where usize < 32 bits
-        let vendor_length: usize = u32::from_le_bytes(buffer).try_into()?;
-        let mut buffer = vec![0; vendor_length];
-        cursor.read_exact(&mut buffer)?;
-        let vendor = String::from_utf8(buffer)?;
-        let mut buffer = [0; 4];
-        cursor.read_exact(&mut buffer)?;
-        let comment_count = u32::from_le_bytes(buffer);
-        let mut comments: Vec<(String, String)> = Vec::new();
-        for _ in 0..comment_count {
-            let mut buffer = [0; 4];
-            cursor.read_exact(&mut buffer)?;
-            // only panics on platforms where usize < 32 bits
-            let comment_length: usize = u32::from_le_bytes(buffer).try_into()?;
-            let mut buffer = vec![0; comment_length];
-            cursor.read_exact(&mut buffer)?;
-            let comment = String::from_utf8(buffer.clone())?;
-            let pair = comment
-                .split_once('=')
-                .map(|(tag, value)| (tag.to_string(), value.to_string()))
-                .ok_or(Error::MalformedComment(comment))?;
-            comments.push(pair);
-        }
-        Ok(Self::new(vendor, comments))
+        Self::read_from_opts(f_in, &ReadOptions::default())
     }
 
-    /// Convenience function for reading comments from a path.
+    /// Read a `Tag` from a reader, using the given [`ReadOptions`]. See
+    /// [`read_from`](Self::read_from) for more info.
     /// # Errors
-    /// This function will error for the same reasons as [`read_from`](Self::read_from)
-    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let file = File::open(path)?;
-        Self::read_from(file)
+    /// This function will error for the same reasons as [`read_from`](Self::read_from), except
+    /// where relaxed by the given options.
+    pub fn read_from_opts<R: Read + Seek>(f_in: R, options: &ReadOptions) -> Result<Self> {
+        Ok(read_comments(f_in, options, None)?.into_tag())
     }
 
-    /// Writes tags to a writer. This function expects the writer to already contain an existing
-    /// opus stream. This function reads the existing stream, copies it **into memory**, replaces the
-    /// comment header, and dumps the whole stream back into the file.
+    /// Read a `Tag` from a reader, without reading anything past the page the comment header ends
+    /// on. This is guaranteed even if the comment header is followed by other pages of the same
+    /// or other logical streams, which makes it suitable for partial files, e.g. the first few
+    /// kilobytes of a remote file fetched with an HTTP range request.
+    ///
+    /// Unlike [`read_from`](Self::read_from), the pages are read into memory before the comment
+    /// header is parsed.
     /// # Errors
-    /// This function will error if:
-    /// - No opus stream exists in the target
-    /// - The ogg stream is shorter than expected (e.g. doesn't include the first or second packets)
-    /// - A comment in this Tag object is too big for the opus spec (some string is longer than [`u32::MAX`] bytes,
-    ///   or the object contains more than [`u32::MAX`] comments)
-    /// - An unspecified error occurs while reading ogg packets from the target
-    /// - An error occurs while writing an ogg packet to the target
-    /// - An error occurs while seeking through the target
-    /// - An error occurs while copying the finished ogg stream from memory back to the target
-    pub fn write_to<W: StorageFile>(&self, mut f_in: W) -> Result<()> {
-        let mut f_out_raw: Vec<u8> = vec![];
-        let mut cursor = Cursor::new(&mut f_out_raw);
-
-        let mut reader = PacketReader::new(&mut f_in);
-        let mut writer = PacketWriter::new(&mut cursor);
+    /// This function will error for the same reasons as [`read_from`](Self::read_from). If the
+    /// data ends before the comment header is complete, including in the middle of a page, the
+    /// error is [`Error::MissingPacket`].
+    pub fn read_header_only<R: Read + Seek>(mut f_in: R) -> Result<Self> {
+        stream::skip_id3v2(&mut f_in)?;
+        stream::reject_matroska(&mut f_in)?;
+        let header_pages = match stream::read_header_pages(f_in) {
+            Err(Error::DataError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(Error::MissingPacket);
+            }
+            result => result?,
+        };
+        Ok(read_comments(Cursor::new(header_pages), &ReadOptions::default(), None)?.into_tag())
+    }
 
-        // first packet
-        {
-            let first_packet = reader.read_packet()?.ok_or(Error::MissingPacket)?;
-            writer.write_packet(
-                first_packet.data.clone(),
-                first_packet.stream_serial(),
-                get_end_info(&first_packet),
-                first_packet.absgp_page(),
-            )?;
-        }
+    /// Read a `Tag` from the opus logical stream with the given serial number. This is useful for
+    /// chained files, which consist of several opus streams one after another, each with their own
+    /// tags. [`read_from`](Self::read_from) reads the tags of the first opus stream.
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from), or with
+    /// [`Error::SerialNotFound`] if no logical stream with the given serial exists.
+    pub fn read_from_serial<R: Read + Seek>(f_in: R, serial: u32) -> Result<Self> {
+        Ok(read_comments(f_in, &ReadOptions::default(), Some(serial))?.into_tag())
+    }
 
-        // second packet, which is the comment header
-        {
-            let comment_header_packet = reader.read_packet()?.ok_or(Error::MissingPacket)?;
-            let new_pack_data = self.to_packet_data()?;
-            writer.write_packet(
-                new_pack_data,
-                comment_header_packet.stream_serial(),
-                PacketWriteEndInfo::EndPage,
-                comment_header_packet.absgp_page(),
-            )?;
+    /// Read a `Tag` from a reader, additionally reporting the keys which appeared in multiple
+    /// casings (e.g. both `Artist` and `ARTIST`). Since keys are lowercased when read, the values
+    /// of such keys are merged silently by [`read_from`](Self::read_from).
+    ///
+    /// The reported keys are lowercase, in the order in which they first appear in the stream.
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from)
+    pub fn read_from_report_casing<R: Read + Seek>(f_in: R) -> Result<(Self, Vec<String>)> {
+        let contents = read_comments(f_in, &ReadOptions::default(), None)?;
+
+        let mut casings: Vec<(String, Vec<&str>)> = Vec::new();
+        for (key, _) in &contents.comments {
+            let lowercase = key.to_ascii_lowercase();
+            match casings.iter_mut().find(|(k, _)| *k == lowercase) {
+                Some((_, seen)) if !seen.contains(&key.as_str()) => seen.push(key),
+                Some(_) => {}
+                None => casings.push((lowercase, vec![key])),
+            }
+        }
+        let collisions = casings
+            .into_iter()
+            .filter(|(_, seen)| seen.len() > 1)
+            .map(|(key, _)| key)
+            .collect();
+
+        Ok((contents.into_tag(), collisions))
+    }
+
+    /// Read a `Tag` from a byte slice containing an opus stream, such as a memory-mapped file.
+    ///
+    /// Only the pages up to the comment header are read from the slice, and the slice is never
+    /// copied as a whole.
+    ///
+    /// This function is safe to call on untrusted input: it never panics, and never allocates
+    /// more than a small multiple of the length of the slice, since every length field is checked
+    /// against the remaining data before anything is allocated for it. The same holds for
+    /// decoding the pictures of the returned tag. This is checked by the fuzz target in the
+    /// `fuzz` directory of the repository.
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from)
+    pub fn read_from_slice(data: &[u8]) -> Result<Self> {
+        Self::read_from(Cursor::new(data))
+    }
+
+    /// Parse a `Tag` directly from a comment header packet (starting with `OpusTags`), bypassing
+    /// the ogg layer. This is the inverse of encoding a tag into a comment header.
+    /// # Errors
+    /// This function will error with [`Error::NotOpus`] if the packet doesn't start with the
+    /// `OpusTags` magic signature, and for the same reasons as [`read_from`](Self::read_from)
+    /// concerning the comment header itself.
+    pub fn from_comment_header(packet: &[u8]) -> Result<Self> {
+        if !packet.starts_with(b"OpusTags") {
+            return Err(Error::NotOpus);
+        }
+        Ok(header_contents(packet, &ReadOptions::default())?.into_tag())
+    }
+
+    /// Read a `Tag` from a reader which doesn't implement [`Seek`], such as a network socket or
+    /// stdin.
+    ///
+    /// The pages up to the end of the comment header are buffered in memory and parsed from
+    /// there. Nothing past the page the comment header ends on is read from the reader.
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from)
+    pub fn read_from_unseekable<R: Read>(f_in: R) -> Result<Self> {
+        let data = stream::read_header_pages(f_in)?;
+        Self::read_from(Cursor::new(data))
+    }
+
+    /// Read a `Tag` from a gzip-compressed opus stream, such as a `.opus.gz` file.
+    ///
+    /// Only the compressed data up to the end of the comment header is decompressed, and only
+    /// those pages are buffered in memory (see [`read_from_unseekable`](Self::read_from_unseekable)).
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from), or if
+    /// the data is not valid gzip data.
+    #[cfg(feature = "gz")]
+    pub fn read_from_gz<R: Read>(f_in: R) -> Result<Self> {
+        Self::read_from_unseekable(flate2::read::MultiGzDecoder::new(f_in))
+    }
+
+    /// Read a `Tag` from a zstd-compressed opus stream, such as a `.opus.zst` file.
+    ///
+    /// Only the compressed data up to the end of the comment header is decompressed, and only
+    /// those pages are buffered in memory (see [`read_from_unseekable`](Self::read_from_unseekable)).
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from), or if
+    /// the data is not valid zstd data.
+    #[cfg(feature = "zstd")]
+    pub fn read_from_zst<R: Read>(f_in: R) -> Result<Self> {
+        Self::read_from_unseekable(zstd::Decoder::new(f_in)?)
+    }
+
+    /// Reads only the vendor string from a reader, without parsing any of the comments. This is
+    /// much cheaper than [`read_from`](Self::read_from) for files with many comments.
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from), apart
+    /// from those concerning the comments.
+    pub fn read_vendor<R: Read + Seek>(mut f_in: R) -> Result<String> {
+        stream::skip_id3v2(&mut f_in)?;
+        let mut reader = PacketReader::new(f_in);
+        let (_, header_packet) = read_opus_headers(&mut reader, None)?;
+        let (vendor, _) = comment_header::parse_vendor(&header_packet.data)?;
+        Ok(vendor.to_string())
+    }
+
+    /// Checks whether a reader contains any pictures, without parsing the other comments or
+    /// decoding any picture data. This is much cheaper than calling
+    /// [`has_pictures`](Self::has_pictures) on the result of [`read_from`](Self::read_from).
+    /// # Errors
+    /// This function will error for the same reasons as [`read_vendor`](Self::read_vendor), or if
+    /// a comment before the first picture is truncated.
+    pub fn file_has_picture<R: Read + Seek>(mut f_in: R) -> Result<bool> {
+        stream::skip_id3v2(&mut f_in)?;
+        let mut reader = PacketReader::new(f_in);
+        let (_, header_packet) = read_opus_headers(&mut reader, None)?;
+        comment_header::has_key(&header_packet.data, PICTURE_BLOCK_TAG)
+    }
+
+    /// Convenience function for reading comments from a path.
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from)
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        Self::read_from(file)
+    }
+
+    /// Computes the duration of the audio in an opus stream from the granule position of its
+    /// last page, without decoding any audio. The pre-skip from the identification header (see
+    /// [`OpusHead::pre_skip`]) is subtracted, and the sample rate of opus granule positions is
+    /// always 48 kHz.
+    ///
+    /// The whole stream is walked page by page, but only the page headers are read. For chained
+    /// streams, only the first opus stream is taken into account.
+    /// # Errors
+    /// This function can error if:
+    /// - The target is not an opus stream, or its identification header is malformed
+    /// - A page in the stream doesn't start with the ogg capture pattern
+    /// - No page of the opus stream has a granule position
+    /// - An error occurs while reading from or seeking through the target
+    pub fn duration<R: Read + Seek>(mut f_in: R) -> Result<Duration> {
+        stream::skip_id3v2(&mut f_in)?;
+        let start = f_in.stream_position()?;
+        let (first_packet, _) = read_opus_headers(&mut PacketReader::new(&mut f_in), None)?;
+        let head = OpusHead::from_bytes(&first_packet.data)?;
+        f_in.seek(SeekFrom::Start(start))?;
+        let granule_position = stream::last_granule_position(&mut f_in)?;
+
+        let samples = granule_position.saturating_sub(u64::from(head.pre_skip));
+        let nanos = (samples % 48_000) * 1_000_000_000 / 48_000;
+        Ok(Duration::from_secs(samples / 48_000) + Duration::from_nanos(nanos))
+    }
+
+    /// Walks the pages of an ogg stream and collects diagnostic information about its layout.
+    ///
+    /// The stream is read page by page, without reassembling packets. See [`StreamStats`] for
+    /// more info.
+    /// # Errors
+    /// This function can error if:
+    /// - A page in the stream doesn't start with the ogg capture pattern
+    /// - A page header is shorter than expected
+    /// - The first logical stream ends before its comment header
+    pub fn stream_stats<R: Read + Seek>(f_in: R) -> Result<StreamStats> {
+        stream::stream_stats(f_in)
+    }
+
+    /// Compares this tag against another tag, treating `old` as the original. See [`TagDiff`]
+    /// for more info.
+    #[must_use]
+    pub fn diff(&self, old: &Self) -> TagDiff {
+        TagDiff::new(old, self)
+    }
+
+    /// Reads the tags of an existing opus stream and reports how writing this tag to it would
+    /// change them, without writing anything. See [`TagDiff`] for more info.
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from).
+    pub fn diff_against_file<R: Read + Seek>(&self, f_in: R) -> Result<TagDiff> {
+        let old = Self::read_from(f_in)?;
+        Ok(self.diff(&old))
+    }
+
+    /// Returns whether writing this tag to an existing opus stream with the default
+    /// [`WriteOptions`] would produce a comment header identical to the one already there. This
+    /// allows skipping unnecessary rewrites.
+    ///
+    /// Unlike the comparison described on [`Tag`], this compares the encoded comment headers, so
+    /// the case and order of the keys and the data following the comments have to match as well.
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from).
+    pub fn matches_file<R: Read + Seek>(&self, mut f_in: R) -> Result<bool> {
+        stream::skip_id3v2(&mut f_in)?;
+        let mut reader = PacketReader::new(f_in);
+        let (_, header_packet) = read_opus_headers(&mut reader, None)?;
+        Ok(self.to_packet_data(&WriteOptions::default())? == header_packet.data)
+    }
+
+    /// Writes tags to a writer. This function expects the writer to already contain an existing
+    /// opus stream. This function reads the existing stream, copies it **into memory**, replaces the
+    /// comment header, and dumps the whole stream back into the file.
+    ///
+    /// If the new comment header spans a different number of pages than the original one, the
+    /// pages following it are renumbered so that the page sequence numbers stay contiguous. The
+    /// granule positions of the audio pages are preserved. Comment headers which don't fit on a
+    /// single page are split into continued pages, and every page of the new stream is verified
+    /// before anything is written back to the target.
+    ///
+    /// Other logical streams multiplexed with the opus stream, such as an Ogg Skeleton stream, are
+    /// copied unchanged. The comment header replaced is the packet following the identification
+    /// header of the first opus stream, wherever it is in the file.
+    ///
+    /// As a fast path, the comment header is patched in place without reading or rewriting the
+    /// audio data if all of the following hold:
+    /// - The tag has no pictures
+    /// - The stream contains a single logical stream, and the comment header pages contain no
+    ///   other packets
+    /// - The new comment header is no longer than the original one, and shorter by at most
+    ///   [`WriteOptions::padding`] bytes
+    /// - The new comment header can be padded to the length of the original one, i.e. the data
+    ///   following the comments is padding rather than binary data, or no padding is needed
+    ///
+    /// The new comment header is padded with zero bytes to the length of the original one, so
+    /// the size of the file doesn't change. Otherwise, e.g. after removing a large picture, the
+    /// stream is rewritten and shrinks accordingly.
+    /// # Errors
+    /// This function will error if:
+    /// - No opus stream exists in the target, or the target is a Matroska/`WebM` file
+    /// - The ogg stream is shorter than expected (e.g. doesn't include the first or second packets)
+    /// - A comment in this Tag object is too big for the opus spec (some string is longer than [`u32::MAX`] bytes,
+    ///   or the object contains more than [`u32::MAX`] comments)
+    /// - An unspecified error occurs while reading ogg packets from the target
+    /// - An error occurs while writing an ogg packet to the target
+    /// - A page of the new stream is malformed (see [`Error::InvalidPage`])
+    /// - An error occurs while seeking through the target
+    /// - An error occurs while copying the finished ogg stream from memory back to the target
+    pub fn write_to<W: StorageFile>(&self, f_in: W) -> Result<()> {
+        self.write_to_opts(f_in, &WriteOptions::default())
+    }
+
+    /// Writes tags to a writer, using the given [`WriteOptions`]. See [`write_to`](Self::write_to)
+    /// for more info.
+    /// # Errors
+    /// This function will error for the same reasons as [`write_to`](Self::write_to)
+    pub fn write_to_opts<W: StorageFile>(&self, mut f_in: W, options: &WriteOptions) -> Result<()> {
+        stream::reject_matroska(&mut f_in)?;
+        if !self.has_pictures() && self.write_in_place(&mut f_in, options)? {
+            return Ok(());
         }
 
+        let mut f_out_raw: Vec<u8> = vec![];
+        let mut cursor = Cursor::new(&mut f_out_raw);
+
+        let mut reader = PacketReader::new(&mut f_in);
+        let mut writer = PacketWriter::new(&mut cursor);
+
+        let mut new_pack_data = Some(self.to_packet_data(options)?);
+        let mut opus_serial = None;
+
         while let Some(packet) = reader.read_packet()? {
             let stream_serial = packet.stream_serial();
+            if opus_serial == Some(stream_serial) {
+                // the packet following the identification header is the comment header
+                if let Some(new_pack_data) = new_pack_data.take() {
+                    writer.write_packet(
+                        new_pack_data,
+                        stream_serial,
+                        PacketWriteEndInfo::EndPage,
+                        packet.absgp_page(),
+                    )?;
+                    continue;
+                }
+            } else if opus_serial.is_none() {
+                // all beginning-of-stream pages precede the data pages, so if the opus stream
+                // hasn't started yet, it never will
+                if !packet.first_in_stream() {
+                    return Err(Error::NotOpus);
+                }
+                if packet.data.starts_with(b"OpusHead") {
+                    opus_serial = Some(stream_serial);
+                }
+            }
             let end_info = get_end_info(&packet);
             let absgp_page = packet.absgp_page();
             writer.write_packet(packet.data, stream_serial, end_info, absgp_page)?;
         }
+        if opus_serial.is_none() {
+            return Err(Error::NotOpus);
+        }
+        if new_pack_data.is_some() {
+            return Err(Error::MissingPacket);
+        }
         // stream ended
 
-        f_in.seek(std::io::SeekFrom::Start(0))?;
-        f_in.set_len(f_out_raw.len() as u64)?;
+        stream::verify_pages(&f_out_raw)?;
+
+        // truncate only after the new contents were written completely, so that a failed write
+        // never leaves the target resized around partial contents
+        let new_len = u64::try_from(f_out_raw.len())?;
+        f_in.seek(SeekFrom::Start(0))?;
         f_in.write_all(&f_out_raw)?;
+        f_in.set_len(new_len)?;
+
+        Ok(())
+    }
+
+    /// Sets the output gain in the identification header of an opus stream, in Q7.8 dB format
+    /// (see [`OpusHead::output_gain`]).
+    ///
+    /// Unlike [`write_to`](Self::write_to), this edits the page holding the identification header
+    /// in place, without touching the comment header or the audio data.
+    /// # Errors
+    /// This function will error if:
+    /// - The target is empty or doesn't start with an ogg page
+    /// - No page of the stream starts with a valid opus identification header
+    /// - An error occurs while reading from, seeking through or writing to the target
+    pub fn set_output_gain<W: StorageFile>(mut f_in: W, gain_q8: i16) -> Result<()> {
+        f_in.seek(SeekFrom::Start(0))?;
+        stream::skip_id3v2(&mut f_in)?;
+        let mut header = PageHeader::read_from(&mut f_in)?.ok_or(Error::MissingPacket)?;
+
+        // other logical streams, such as a skeleton, may begin before the opus stream
+        let (offset, header_len, mut page) = loop {
+            let header_len = 27 + header.lacing.len();
+            let offset = f_in.stream_position()? - u64::try_from(header_len)?;
+            let mut page = vec![0; header_len + header.body_len()];
+            f_in.seek(SeekFrom::Start(offset))?;
+            f_in.read_exact(&mut page)?;
+            if page[header_len..].starts_with(b"OpusHead") {
+                break (offset, header_len, page);
+            }
+            header = PageHeader::read_from(&mut f_in)?.ok_or(Error::NotOpus)?;
+        };
 
+        let mut head = OpusHead::from_bytes(&page[header_len..])?;
+        head.output_gain = gain_q8;
+        let new_head = head.to_bytes();
+        page[header_len..header_len + new_head.len()].copy_from_slice(&new_head);
+        stream::update_checksum(&mut page);
+
+        f_in.seek(SeekFrom::Start(offset))?;
+        f_in.write_all(&page)?;
         Ok(())
     }
 
@@ -364,34 +1432,409 @@ impl Tag {
         self.write_to(file)
     }
 
-    fn to_packet_data(&self) -> Result<Vec<u8>> {
-        let mut output = vec![];
+    /// Reads the comments of the file at the given path, applies `f` to them and writes them back
+    /// to the same file. Anything not touched by `f`, such as the vendor string, is written back
+    /// exactly as it was read.
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from) and
+    /// [`write_to`](Self::write_to)
+    pub fn edit_file<P: AsRef<Path>>(path: P, f: impl FnOnce(&mut Self)) -> Result<()> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut tag = Self::read_from(&mut file)?;
+        f(&mut tag);
+        file.rewind()?;
+        tag.write_to(file)
+    }
+
+    /// Reads the tags of an opus stream and writes them back unchanged, reporting whether the
+    /// result is byte-for-byte identical to the original. This holds for any stream which
+    /// [`write_to`](Self::write_to) supports, since the order and case of the comments and any
+    /// data following them are preserved.
+    /// # Errors
+    /// This function will error for the same reasons as [`read_from`](Self::read_from) and
+    /// [`write_to`](Self::write_to).
+    pub fn roundtrip_bytes(original: &[u8]) -> Result<bool> {
+        let tag = Self::read_from_slice(original)?;
+        let mut output = Cursor::new(original.to_vec());
+        tag.write_to(&mut output)?;
+        Ok(output.get_ref() == original)
+    }
+
+    /// Writes to a path atomically: the new stream is written to a temporary file next to the
+    /// original (named `.<file name>.tmp`), which is then renamed over the original. Readers
+    /// never see a half-written file, even if the process crashes while writing. The permissions
+    /// of the original file are kept.
+    /// # Errors
+    /// This function will error for the same reasons as [`write_to`](Self::write_to), or if the
+    /// temporary file can't be created or renamed. The temporary file is removed on error.
+    pub fn write_to_path_atomic<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut file_name = std::ffi::OsString::from(".");
+        file_name.push(path.file_name().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "path has no file name")
+        })?);
+        file_name.push(".tmp");
+        let temp_path = path.with_file_name(file_name);
+
+        let mut data = Cursor::new(std::fs::read(path)?);
+        self.write_to(&mut data)?;
+
+        let result = (|| -> Result<()> {
+            let mut temp_file = File::create(&temp_path)?;
+            temp_file.write_all(data.get_ref())?;
+            temp_file.set_permissions(std::fs::metadata(path)?.permissions())?;
+            temp_file.sync_all()?;
+            std::fs::rename(&temp_path, path)?;
+            Ok(())
+        })();
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    /// Writes a brand-new Opus stream to a writer, consisting of the given identification header,
+    /// the comment header built from this Tag, and the given audio packets.
+    ///
+    /// The audio packets are expected to be raw Opus packets, as produced by an encoder. The granule
+    /// position of every page is computed from the TOC byte of the packets it contains, and pages
+    /// are ended once they hold roughly 4 KiB of audio data. The stream is given a random serial
+    /// number.
+    /// # Errors
+    /// This function will error if:
+    /// - A comment in this Tag object is too big for the opus spec (see [`write_to`](Self::write_to))
+    /// - An error occurs while writing an ogg packet to the writer
+    pub fn build_stream<W: Write>(
+        &self,
+        head: &OpusHead,
+        audio_packets: impl Iterator<Item = Vec<u8>>,
+        out: W,
+    ) -> Result<()> {
+        // only the low 32 bits of a random hash are needed
+        #[allow(clippy::cast_possible_truncation)]
+        let serial = RandomState::new().hash_one(0u8) as u32;
+        let mut writer = PacketWriter::new(out);
+        let mut audio_packets = audio_packets.peekable();
+
+        writer.write_packet(head.to_bytes(), serial, PacketWriteEndInfo::EndPage, 0)?;
+
+        let end_info = if audio_packets.peek().is_some() {
+            PacketWriteEndInfo::EndPage
+        } else {
+            PacketWriteEndInfo::EndStream
+        };
+        writer.write_packet(
+            self.to_packet_data(&WriteOptions::default())?,
+            serial,
+            end_info,
+            0,
+        )?;
+
+        let mut granule = 0;
+        let mut page_len = 0;
+        let mut page_segments = 0;
+        while let Some(packet) = audio_packets.next() {
+            granule += head::packet_samples(&packet);
+            page_len += packet.len();
+            page_segments += lacing_segments(&packet);
+
+            let end_info = match audio_packets.peek() {
+                None => PacketWriteEndInfo::EndStream,
+                Some(next)
+                    if page_len >= TARGET_PAGE_SIZE
+                        || page_segments + lacing_segments(next) > 255 =>
+                {
+                    page_len = 0;
+                    page_segments = 0;
+                    PacketWriteEndInfo::EndPage
+                }
+                Some(_) => PacketWriteEndInfo::NormalPacket,
+            };
+            writer.write_packet(packet, serial, end_info, granule)?;
+        }
+
+        Ok(())
+    }
+
+    /// Computes a hash of the vendor string and all comments, including the pictures (as their
+    /// base64 encoding). The comments are hashed in sorted order, so the hash doesn't depend on
+    /// the order in which they were added. Tags which are equal always have the same hash.
+    ///
+    /// The hash is stable across runs, platforms and versions of this crate, so it can be stored
+    /// e.g. as a cache key. It is not a cryptographic hash.
+    #[must_use]
+    pub fn content_hash(&self) -> u64 {
+        let options = WriteOptions {
+            sort: true,
+            ..Default::default()
+        };
+        let mut hash = fnv1a(FNV_OFFSET_BASIS, self.vendor.as_bytes());
+        // hash the lengths as well, so that the boundaries between the strings are unambiguous
+        let hash_str = |hash, part: &str| {
+            let hash = fnv1a(hash, &(part.len() as u64).to_le_bytes());
+            fnv1a(hash, part.as_bytes())
+        };
+        for (key, value) in self.entries(&options) {
+            hash = hash_str(hash_str(hash, key), value);
+        }
+        hash
+    }
+
+    /// Returns the comments as `TAG=VALUE` lines, including the picture entries, in the exact
+    /// order and form in which [`write_to`](Self::write_to) would encode them.
+    #[must_use]
+    pub fn comment_lines(&self) -> Vec<String> {
+        self.entries(&WriteOptions::default())
+            .into_iter()
+            .map(|(tag, value)| format!("{tag}={value}"))
+            .collect()
+    }
+
+    /// Formats the comments in the text format of the `vorbiscomment` tool, with one `TAG=VALUE`
+    /// line per comment, in the same order and form as [`comment_lines`](Self::comment_lines).
+    ///
+    /// Values are escaped as with `vorbiscomment --escapes`, so that multi-line values fit on one
+    /// line: backslashes, line feeds, carriage returns and NUL characters are written as `\\`,
+    /// `\n`, `\r` and `\0`. Pictures are included as base64-encoded `METADATA_BLOCK_PICTURE`
+    /// lines, like `vorbiscomment` does. The vendor string is not included.
+    #[must_use]
+    pub fn to_vorbiscomment_text(&self) -> String {
+        let mut text = String::new();
+        for (tag, value) in self.entries(&WriteOptions::default()) {
+            text.push_str(tag);
+            text.push('=');
+            text.push_str(&utils::escape_value(value));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Parses comments in the text format of the `vorbiscomment` tool, as written by
+    /// [`to_vorbiscomment_text`](Self::to_vorbiscomment_text). Escape sequences in the values
+    /// are unescaped, and empty lines are skipped. The vendor string of the returned tag is
+    /// empty.
+    /// # Errors
+    /// This function will error with [`Error::MalformedComment`] if a line is not in TAG=VALUE
+    /// format, or if a value contains an unknown escape sequence.
+    pub fn from_vorbiscomment_text(text: &str) -> Result<Self> {
+        let mut comments = Vec::new();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            let comment = line
+                .split_once('=')
+                .and_then(|(key, value)| Some((key.to_string(), utils::unescape_value(value)?)))
+                .ok_or_else(|| Error::MalformedComment(line.to_string()))?;
+            comments.push(comment);
+        }
+        Ok(Self::new(String::new(), comments))
+    }
+
+    /// Returns the (key, value) pairs of every comment, including the picture entries, in the
+    /// order in which they are encoded.
+    ///
+    /// Unless sorting, the comments are encoded in the order of the layout the tag was created
+    /// from, with their original keys. The values of every key fill the slots of that key in the
+    /// layout one by one, and the values left over are encoded afterwards with lowercase keys.
+    fn entries(&self, options: &WriteOptions) -> Vec<(&str, &str)> {
+        let mut entries: Vec<(&str, &str)> = Vec::new();
+        if options.sort {
+            entries.extend(self.comments.iter().flat_map(|(tag, values)| {
+                values
+                    .iter()
+                    .map(move |value| (tag.as_str(), value.as_str()))
+            }));
+            entries.sort_unstable();
+        } else {
+            let mut used: HashMap<String, usize> = HashMap::new();
+            for key in &self.layout {
+                let lowercase = key.to_ascii_lowercase();
+                let Some(values) = self.comments.get(&lowercase) else {
+                    continue;
+                };
+                let used = used.entry(lowercase).or_insert(0);
+                if let Some(value) = values.get(*used) {
+                    entries.push((key, value));
+                    *used += 1;
+                }
+            }
+            for (tag, values) in &self.comments {
+                let used = used.get(tag).copied().unwrap_or(0);
+                entries.extend(
+                    values
+                        .iter()
+                        .skip(used)
+                        .map(|value| (tag.as_str(), value.as_str())),
+                );
+            }
+        }
+        // keys from the layout keep their original case
+        let is_picture = |tag: &str| tag.eq_ignore_ascii_case(PICTURE_BLOCK_TAG);
+        match options.picture_placement {
+            PicturePlacement::InOrder => {}
+            PicturePlacement::First => entries.sort_by_key(|(tag, _)| !is_picture(tag)),
+            PicturePlacement::Last => entries.sort_by_key(|(tag, _)| is_picture(tag)),
+        }
+        entries
+    }
+
+    /// Replaces the comment header of a stream by patching its pages in place. Returns false
+    /// without writing anything if the new comment header doesn't fit the original pages, leaving
+    /// the target at the position it was at. See [`write_to`](Self::write_to) for the exact
+    /// conditions.
+    fn write_in_place<W: StorageFile>(&self, f_in: &mut W, options: &WriteOptions) -> Result<bool> {
+        let start = f_in.stream_position()?;
+        let header_data = stream::read_header_pages(&mut *f_in)?;
+        f_in.seek(SeekFrom::Start(start))?;
+        let Some(header_data) = self.patch_header_pages(header_data, options)? else {
+            return Ok(false);
+        };
+
+        f_in.write_all(&header_data)?;
+        Ok(true)
+    }
+
+    /// Replaces the comment header in the raw header pages of a stream (see
+    /// [`stream::read_header_pages`]). Returns None if the new comment header doesn't fit.
+    fn patch_header_pages(
+        &self,
+        mut header_data: Vec<u8>,
+        options: &WriteOptions,
+    ) -> Result<Option<Vec<u8>>> {
+        // the (header length, body range) of every page
+        let mut pages = Vec::new();
+        let mut position = 0;
+        let mut serial = None;
+        let mut packets = 0;
+        while let Some(header) = PageHeader::read_from(&header_data[position..])? {
+            if *serial.get_or_insert(header.serial) != header.serial {
+                return Ok(None);
+            }
+            packets += header.packets_ended();
+            let body_start = position + 27 + header.lacing.len();
+            let body_end = body_start + header.body_len();
+            if body_end > header_data.len() {
+                return Err(Error::MissingPacket);
+            }
+            pages.push((header.lacing, body_start..body_end));
+            position = body_end;
+        }
+        // the identification header must fill the first page, and the comment header must end
+        // at the end of the last page. If the last page is shared with the start of an audio
+        // packet which continues on the next page, its last lacing value is 255.
+        let Some(((first_lacing, _), comment_pages)) = pages.split_first() else {
+            return Ok(None);
+        };
+        let Some((last_lacing, _)) = comment_pages.last() else {
+            return Ok(None);
+        };
+        if packets != 2
+            || first_lacing.last().is_none_or(|&len| len == 255)
+            || last_lacing.last().is_none_or(|&len| len == 255)
+        {
+            return Ok(None);
+        }
+
+        let old_len: usize = comment_pages.iter().map(|(_, body)| body.len()).sum();
+        let mut new_packet = self.to_packet_data(options)?;
+        let is_padding = self.trailing_data.first().is_none_or(|byte| byte & 1 == 0);
+        if new_packet.len() > old_len || (new_packet.len() < old_len && !is_padding) {
+            return Ok(None);
+        }
+        // the padding left behind would be read back as trailing data and kept by every later
+        // write, so a header which shrank by more than the requested padding is rewritten instead
+        if old_len - new_packet.len() > options.padding {
+            return Ok(None);
+        }
+        new_packet.resize(old_len, 0);
+
+        let mut remaining = new_packet.as_slice();
+        let mut page_start = pages[0].1.end;
+        for (_, body) in comment_pages {
+            let (chunk, rest) = remaining.split_at(body.len());
+            header_data[body.clone()].copy_from_slice(chunk);
+            stream::update_checksum(&mut header_data[page_start..body.end]);
+            remaining = rest;
+            page_start = body.end;
+        }
+        stream::verify_pages(&header_data)?;
+        Ok(Some(header_data))
+    }
+
+    /// Computes the length in bytes of the comment header packet which
+    /// [`write_to`](Self::write_to) would write for this tag, without encoding it.
+    #[must_use]
+    pub fn encoded_len(&self) -> usize {
+        let comments_len: usize = self
+            .entries(&WriteOptions::default())
+            .iter()
+            .map(|(tag, value)| 4 + tag.len() + 1 + value.len())
+            .sum();
+        8 + 4 + self.vendor.len() + 4 + comments_len + self.trailing_data.len()
+    }
+
+    /// Computes how much longer the comment header packet written by
+    /// [`write_to`](Self::write_to) would be than the one currently in the reader, in bytes. A
+    /// negative result means the new comment header is shorter.
+    ///
+    /// This is useful for telling whether the in-place fast path of `write_to` applies, which
+    /// requires the delta to be at most zero and no less than the negated
+    /// [`WriteOptions::padding`].
+    /// # Errors
+    /// This function will error for the same reasons as [`read_vendor`](Self::read_vendor).
+    pub fn header_size_delta<R: Read + Seek>(&self, mut f_in: R) -> Result<i64> {
+        stream::skip_id3v2(&mut f_in)?;
+        let mut reader = PacketReader::new(f_in);
+        let (_, header_packet) = read_opus_headers(&mut reader, None)?;
+        Ok(i64::try_from(self.encoded_len())? - i64::try_from(header_packet.data.len())?)
+    }
+
+    fn to_packet_data(&self, options: &WriteOptions) -> Result<Vec<u8>> {
+        let entries = self.entries(options);
+        let vendor = &self.vendor;
+
+        // size the output up front, since the picture entries can be very large
+        let comments_len: usize = entries
+            .iter()
+            .map(|(tag, value)| 4 + tag.len() + 1 + value.len())
+            .sum();
+        let trailing_len = self.trailing_data.len().max(options.padding);
+        let mut output = Vec::with_capacity(8 + 4 + vendor.len() + 4 + comments_len + trailing_len);
+
         // magic signature
         output.extend_from_slice(b"OpusTags");
 
         // encode vendor
-        let vendor = &self.vendor;
         let vendor_length: u32 = vendor.len().try_into().map_err(|_| Error::TooBigError)?;
         output.extend_from_slice(&vendor_length.to_le_bytes());
         output.extend_from_slice(vendor.as_bytes());
 
-        let mut formatted_tags = vec![];
-        for (tag, values) in &self.comments {
-            for value in values {
-                formatted_tags.push(format!("{tag}={value}"));
-            }
-        }
-
-        let num_comments: u32 = formatted_tags
-            .len()
-            .try_into()
-            .map_err(|_| Error::TooBigError)?;
+        let num_comments: u32 = entries.len().try_into().map_err(|_| Error::TooBigError)?;
         output.extend_from_slice(&num_comments.to_le_bytes());
 
-        for tag in formatted_tags {
-            let tag_length: u32 = tag.len().try_into().map_err(|_| Error::TooBigError)?;
-            output.extend_from_slice(&tag_length.to_le_bytes());
+        for (tag, value) in entries {
+            if options.reject_control_chars
+                && !tag.eq_ignore_ascii_case(PICTURE_BLOCK_TAG)
+                && value
+                    .chars()
+                    .any(|c| c < ' ' && !matches!(c, '\t' | '\n' | '\r'))
+            {
+                return Err(Error::ControlCharacter {
+                    key: tag.to_string(),
+                });
+            }
+            let comment_length: u32 = (tag.len() + 1 + value.len())
+                .try_into()
+                .map_err(|_| Error::TooBigError)?;
+            output.extend_from_slice(&comment_length.to_le_bytes());
             output.extend_from_slice(tag.as_bytes());
+            output.push(b'=');
+            output.extend_from_slice(value.as_bytes());
+        }
+
+        output.extend_from_slice(&self.trailing_data);
+        // binary data (marked by the lowest bit of its first byte) must not be extended
+        let is_padding = self.trailing_data.first().is_none_or(|byte| byte & 1 == 0);
+        if is_padding && self.trailing_data.len() < options.padding {
+            output.resize(output.len() + options.padding - self.trailing_data.len(), 0);
         }
 
         Ok(output)
@@ -399,6 +1842,16 @@ impl Tag {
 }
 
 impl Tag {
+    /// Consumes the tag and returns an iterator over its comments as owned (key, value) pairs,
+    /// excluding pictures. A key with several values is yielded once for each value. This avoids
+    /// cloning the comments, e.g. to send them to another thread.
+    pub fn into_comments(self) -> impl Iterator<Item = (String, String)> {
+        self.comments
+            .into_iter()
+            .filter(|(key, _)| key != PICTURE_BLOCK_TAG)
+            .flat_map(|(key, values)| values.into_iter().map(move |value| (key.clone(), value)))
+    }
+
     /// An iterator over the comments of an opus file, excluding pictures.
     ///
     /// See [`CommentsIterator`] for more info.
@@ -409,6 +1862,21 @@ impl Tag {
         }
     }
 
+    /// An iterator over the comments of an opus file sorted by key, excluding pictures.
+    ///
+    /// The sorting is done when this function is called, and does not change how the comments
+    /// are stored or written.
+    pub fn iter_comments_sorted(&self) -> impl Iterator<Item = (&str, &Vec<String>)> {
+        let mut comments: Vec<(&str, &Vec<String>)> = self
+            .comments
+            .iter()
+            .filter(|(key, _)| *key != PICTURE_BLOCK_TAG)
+            .map(|(key, values)| (key.as_str(), values))
+            .collect();
+        comments.sort_unstable_by_key(|(key, _)| *key);
+        comments.into_iter()
+    }
+
     /// An iterator over the images embedded in an opus file.
     ///
     /// See [`PicturesIterator`] for more info.
@@ -421,6 +1889,25 @@ impl Tag {
             })
     }
 
+    /// An iterator over the images embedded in an opus file, decoding them one at a time. Unlike
+    /// [`iter_pictures`](Self::iter_pictures), this returns an empty iterator if there are no
+    /// pictures.
+    ///
+    /// See [`PicturesIterator`] for more info.
+    #[must_use]
+    pub fn pictures_iter(&self) -> PicturesIterator<'_> {
+        PicturesIterator {
+            pictures_iter: self.picture_entries().iter(),
+        }
+    }
+
+    /// An iterator over the images embedded in an opus file, pairing every decoded picture with
+    /// its index in the order returned by [`picture_entries`](Self::picture_entries). The index
+    /// can be used for targeted edits, e.g. with [`remove_picture_at`](Self::remove_picture_at).
+    pub fn pictures_with_index(&self) -> impl Iterator<Item = (usize, Result<Picture>)> {
+        self.pictures_iter().enumerate()
+    }
+
     /// An iterator over the comment keys of an opus file, excluding the picture block key.
     ///
     /// The iterator Item is `&'a str`.
@@ -432,6 +1919,108 @@ impl Tag {
             .filter(|k| *k != PICTURE_BLOCK_TAG)
             .map(AsRef::as_ref)
     }
+
+    /// Gets every comment key, excluding the picture block key, along with the number of values
+    /// it holds. The keys are in the same order as [`keys`](Self::keys).
+    #[must_use]
+    pub fn key_value_counts(&self) -> Vec<(&str, usize)> {
+        self.comments
+            .iter()
+            .filter(|(key, _)| key != PICTURE_BLOCK_TAG)
+            .map(|(key, values)| (key.as_str(), values.len()))
+            .collect()
+    }
+
+    /// An iterator over the comment keys which are not well-known field names, as determined by
+    /// [`is_standard_key`]. This is useful for finding typos (e.g. `arist`) or application
+    /// specific keys.
+    pub fn unknown_keys(&self) -> impl Iterator<Item = &str> {
+        self.keys().filter(|key| !is_standard_key(key))
+    }
+}
+
+/// Builds a tag with an empty vendor string from (key, value) pairs. Keys are lowercased, in the
+/// same way as [`Tag::new`].
+impl FromIterator<(String, String)> for Tag {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        let mut tag = Self::default();
+        tag.extend(iter);
+        tag
+    }
+}
+
+/// Adds (key, value) pairs to a tag. Keys are lowercased, in the same way as [`Tag::new`].
+impl Extend<(String, String)> for Tag {
+    fn extend<I: IntoIterator<Item = (String, String)>>(&mut self, iter: I) {
+        for (mut key, value) in iter {
+            key.make_ascii_lowercase();
+            self.comments.get_or_default(key).push(value);
+        }
+    }
+}
+
+/// Writes each tag to the file at its path using [`Tag::write_to_path`], spreading the files over
+/// the rayon thread pool. Returns the result for every path, in the order of the jobs.
+///
+/// Each file is written independently, so a file which fails to be written doesn't affect the
+/// others.
+#[cfg(feature = "rayon")]
+pub fn write_many(jobs: impl IntoIterator<Item = (PathBuf, Tag)>) -> Vec<(PathBuf, Result<()>)> {
+    use rayon::prelude::*;
+
+    let jobs: Vec<_> = jobs.into_iter().collect();
+    jobs.into_par_iter()
+        .map(|(path, tag)| {
+            let result = tag.write_to_path(&path);
+            (path, result)
+        })
+        .collect()
+}
+
+/// Copies the comments of the file at `from`, including its pictures and vendor string, to the
+/// file at `to`, replacing the comments there. Both files must already contain opus streams.
+/// # Errors
+/// This function will error for the same reasons as [`Tag::read_from`] for `from`, and for the
+/// same reasons as [`Tag::write_to`] for `to`.
+pub fn copy_tags<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<()> {
+    Tag::read_from_path(from)?.write_to_path(to)
+}
+
+/// Reads the comments of every `.opus` file in a directory (not recursively), in the order of
+/// their paths.
+///
+/// Each file is read independently, so a file which fails to parse yields an `Err` for that entry
+/// without affecting the others. If the directory itself (or one of its entries) can't be read,
+/// the error is yielded along with the path of the directory.
+pub fn read_dir<P: AsRef<Path>>(dir: P) -> impl Iterator<Item = (PathBuf, Result<Tag>)> {
+    let dir = dir.as_ref();
+    let mut errors = Vec::new();
+    let mut paths = Vec::new();
+    match std::fs::read_dir(dir) {
+        Ok(entries) => {
+            for entry in entries {
+                match entry {
+                    Ok(entry) => {
+                        let path = entry.path();
+                        let is_opus = path
+                            .extension()
+                            .is_some_and(|extension| extension.eq_ignore_ascii_case("opus"));
+                        if is_opus && path.is_file() {
+                            paths.push(path);
+                        }
+                    }
+                    Err(err) => errors.push((dir.to_path_buf(), Err(err.into()))),
+                }
+            }
+        }
+        Err(err) => errors.push((dir.to_path_buf(), Err(err.into()))),
+    }
+    paths.sort();
+
+    errors.into_iter().chain(paths.into_iter().map(|path| {
+        let tag = Tag::read_from_path(&path);
+        (path, tag)
+    }))
 }
 
 /// A trait representing a file-like reader/writer.
@@ -476,20 +2065,365 @@ impl StorageFile for Cursor<&mut Vec<u8>> {
     }
 }
 
-fn get_end_info(packet: &ogg::Packet) -> PacketWriteEndInfo {
-    if packet.last_in_stream() {
-        PacketWriteEndInfo::EndStream
-    } else if packet.last_in_page() {
-        PacketWriteEndInfo::EndPage
-    } else {
+/// The contents of a comment header, with the keys in their original case.
+struct HeaderContents {
+    vendor: String,
+    comments: Vec<(String, String)>,
+    trailing_data: Vec<u8>,
+}
+
+impl HeaderContents {
+    fn into_tag(self) -> Tag {
+        let mut tag = Tag::new(self.vendor, self.comments);
+        tag.trailing_data = self.trailing_data;
+        tag
+    }
+}
+
+/// Reads the contents of the comment header of an opus stream in a reader. See
+/// [`read_opus_headers`] for how the stream is chosen. An `ID3v2` tag in front of the ogg stream
+/// is skipped.
+fn read_comments<R: Read + Seek>(
+    mut f_in: R,
+    options: &ReadOptions,
+    serial: Option<u32>,
+) -> Result<HeaderContents> {
+    stream::skip_id3v2(&mut f_in)?;
+    stream::reject_matroska(&mut f_in)?;
+    if stream::ends_within_page_header(&mut f_in)? {
+        return Err(Error::MissingPacket);
+    }
+    let mut reader = PacketReader::new(f_in);
+    let (first_packet, mut header_packet) = read_opus_headers(&mut reader, serial)?;
+    if options.scan_for_tags && !header_packet.data.starts_with(b"OpusTags") {
+        header_packet = scan_for_comment_header(&mut reader, first_packet.stream_serial())?;
+    }
+    header_contents(&header_packet.data, options)
+}
+
+/// Searches the next few packets of a logical stream for a packet starting with `OpusTags`. See
+/// [`ReadOptions::scan_for_tags`].
+fn scan_for_comment_header<R: Read + Seek>(
+    reader: &mut PacketReader<R>,
+    serial: u32,
+) -> Result<ogg::Packet> {
+    let mut scanned = 0;
+    while scanned < TAG_SCAN_LIMIT {
+        let packet = next_packet(reader)?.ok_or(Error::MissingPacket)?;
+        if packet.stream_serial() != serial {
+            continue;
+        }
+        if packet.data.starts_with(b"OpusTags") {
+            return Ok(packet);
+        }
+        scanned += 1;
+    }
+    Err(Error::MissingPacket)
+}
+
+/// Parses the contents of a comment header packet.
+fn header_contents(packet: &[u8], options: &ReadOptions) -> Result<HeaderContents> {
+    let header = comment_header::parse(packet, options)?;
+
+    let mut comments = Vec::new();
+    let mut pictures = 0;
+    for (mut tag, mut value) in header.comments() {
+        if options.trim_key_whitespace {
+            tag = tag.trim_matches(' ');
+            value = value.trim_start_matches(' ');
+        }
+        if options.trim_trailing_cr {
+            value = value.strip_suffix('\r').unwrap_or(value);
+        }
+        if let Some(limit) = options.max_pictures
+            && tag.eq_ignore_ascii_case(PICTURE_BLOCK_TAG)
+        {
+            if pictures == limit {
+                if options.reject_excess_pictures {
+                    return Err(Error::TooManyPictures { limit });
+                }
+                continue;
+            }
+            pictures += 1;
+        }
+        comments.push((tag.to_string(), value.to_string()));
+    }
+
+    Ok(HeaderContents {
+        vendor: header.vendor().to_string(),
+        comments,
+        trailing_data: header.trailing_data().to_vec(),
+    })
+}
+
+/// Reads packets until the identification and comment header packets of an opus logical stream
+/// are found. If `serial` is None, the first opus logical stream is used, skipping the
+/// beginning-of-stream packets of other logical streams (e.g. an Ogg Skeleton stream). Otherwise,
+/// packets are skipped until the logical stream with the given serial starts, which may be
+/// further into the file for chained streams. Packets of other streams between the two headers
+/// are skipped either way.
+fn read_opus_headers<R: Read + Seek>(
+    reader: &mut PacketReader<R>,
+    serial: Option<u32>,
+) -> Result<(ogg::Packet, ogg::Packet)> {
+    let first_packet = read_opus_head_packet(reader, serial)?;
+
+    let serial = first_packet.stream_serial();
+    let header_packet = loop {
+        let packet = next_packet(reader)?.ok_or(Error::MissingPacket)?;
+        if packet.stream_serial() == serial {
+            break packet;
+        }
+    };
+
+    Ok((first_packet, header_packet))
+}
+
+/// Reads packets until the identification header of an opus logical stream is found. See
+/// [`read_opus_headers`] for how the stream is chosen.
+fn read_opus_head_packet<R: Read + Seek>(
+    reader: &mut PacketReader<R>,
+    serial: Option<u32>,
+) -> Result<ogg::Packet> {
+    loop {
+        let Some(packet) = next_packet(reader)? else {
+            return Err(serial.map_or(Error::MissingPacket, Error::SerialNotFound));
+        };
+        if let Some(serial) = serial {
+            if packet.stream_serial() != serial {
+                continue;
+            }
+            if packet.first_in_stream() && packet.data.starts_with(b"OpusHead") {
+                return Ok(packet);
+            }
+            return Err(Error::NotOpus);
+        }
+        // all beginning-of-stream pages precede the data pages, so if the opus stream hasn't
+        // started yet, it never will
+        if !packet.first_in_stream() {
+            return Err(Error::NotOpus);
+        }
+        if packet.data.starts_with(b"OpusHead") {
+            return Ok(packet);
+        }
+    }
+}
+
+/// Reads the next packet, treating data which ends in the middle of a page like the end of the
+/// stream, so that truncated input is reported as [`Error::MissingPacket`].
+fn next_packet<R: Read + Seek>(reader: &mut PacketReader<R>) -> Result<Option<ogg::Packet>> {
+    match reader.read_packet() {
+        Err(ogg::OggReadError::ReadError(err))
+            if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+        {
+            Ok(None)
+        }
+        result => Ok(result?),
+    }
+}
+
+fn get_end_info(packet: &ogg::Packet) -> PacketWriteEndInfo {
+    if packet.last_in_stream() {
+        PacketWriteEndInfo::EndStream
+    } else if packet.last_in_page() {
+        PacketWriteEndInfo::EndPage
+    } else {
         PacketWriteEndInfo::NormalPacket
     }
 }
 
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Continues a 64-bit FNV-1a hash with the given bytes. See [`Tag::content_hash`].
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Returns whether a key other than the picture block occurs more than once in the given keys
+/// with a different case, or with other keys in between.
+fn merges_duplicate_keys(keys: &[String]) -> bool {
+    let mut spellings: HashMap<String, &str> = HashMap::new();
+    let mut previous = None;
+    for key in keys {
+        let lowercase = key.to_ascii_lowercase();
+        if lowercase != PICTURE_BLOCK_TAG {
+            match spellings.get(&lowercase) {
+                Some(&spelling) if spelling != key || previous.as_ref() != Some(&lowercase) => {
+                    return true;
+                }
+                Some(_) => {}
+                None => {
+                    spellings.insert(lowercase.clone(), key);
+                }
+            }
+        }
+        previous = Some(lowercase);
+    }
+    false
+}
+
+/// The number of lacing values needed to store a packet in an ogg page.
+const fn lacing_segments(packet: &[u8]) -> usize {
+    packet.len() / 255 + 1
+}
+
 #[cfg(test)]
 mod tests {
+    use base64::prelude::{BASE64_STANDARD, Engine as _};
+
     use super::*;
 
+    /// Encodes a raw comment header packet from a vendor string and a list of comment lines.
+    pub fn comment_header(vendor: &str, comments: &[&[u8]]) -> Vec<u8> {
+        let mut data = b"OpusTags".to_vec();
+        data.extend_from_slice(&u32::try_from(vendor.len()).unwrap().to_le_bytes());
+        data.extend_from_slice(vendor.as_bytes());
+        data.extend_from_slice(&u32::try_from(comments.len()).unwrap().to_le_bytes());
+        for comment in comments {
+            data.extend_from_slice(&u32::try_from(comment.len()).unwrap().to_le_bytes());
+            data.extend_from_slice(comment);
+        }
+        data
+    }
+
+    /// Returns the serial, sequence number and granule position of every page in an ogg stream.
+    fn page_headers(data: &[u8]) -> Vec<(u32, u32, u64)> {
+        let mut pages = vec![];
+        let mut position = 0;
+        while position < data.len() {
+            let header = &data[position..];
+            assert!(header.starts_with(b"OggS"));
+            let absgp = u64::from_le_bytes(header[6..14].try_into().unwrap());
+            let serial = u32::from_le_bytes(header[14..18].try_into().unwrap());
+            let sequence = u32::from_le_bytes(header[18..22].try_into().unwrap());
+            let segments = usize::from(header[26]);
+            let body_len: usize = header[27..27 + segments]
+                .iter()
+                .map(|&l| usize::from(l))
+                .sum();
+            pages.push((serial, sequence, absgp));
+            position += 27 + segments + body_len;
+        }
+        pages
+    }
+
+    /// Asserts that the page sequence numbers of every logical stream start at 0 and are
+    /// contiguous.
+    fn assert_contiguous_pages(data: &[u8]) {
+        let mut next_sequence: HashMap<u32, u32> = HashMap::new();
+        for (serial, sequence, _) in page_headers(data) {
+            let expected = next_sequence.entry(serial).or_insert(0);
+            assert_eq!(
+                sequence, *expected,
+                "gap in page sequence of stream {serial}"
+            );
+            *expected += 1;
+        }
+    }
+
+    /// Encodes a picture comment whose value is long enough to span several ogg pages.
+    fn large_picture_comment(key: &str) -> Vec<u8> {
+        [format!("{key}=").as_bytes(), &vec![b'A'; 70_000]].concat()
+    }
+
+    /// Builds an opus stream which is preceded by an Ogg Skeleton stream.
+    pub fn stream_with_skeleton(header: Vec<u8>) -> Cursor<Vec<u8>> {
+        const SKELETON: u32 = 7;
+        const OPUS: u32 = 1;
+
+        let mut output = Cursor::new(vec![]);
+        let mut writer = PacketWriter::new(&mut output);
+        let fishead = [b"fishead\0".as_slice(), &[0; 56]].concat();
+        writer
+            .write_packet(fishead, SKELETON, PacketWriteEndInfo::EndPage, 0)
+            .unwrap();
+        writer
+            .write_packet(
+                OpusHead::default().to_bytes(),
+                OPUS,
+                PacketWriteEndInfo::EndPage,
+                0,
+            )
+            .unwrap();
+        writer
+            .write_packet(vec![], SKELETON, PacketWriteEndInfo::EndStream, 0)
+            .unwrap();
+        writer
+            .write_packet(header, OPUS, PacketWriteEndInfo::EndPage, 0)
+            .unwrap();
+        writer
+            .write_packet(
+                vec![0xfc, 0xff, 0xfe],
+                OPUS,
+                PacketWriteEndInfo::EndStream,
+                960,
+            )
+            .unwrap();
+        output.set_position(0);
+        output
+    }
+
+    /// Builds a minimal opus stream containing the given comment header packet.
+    fn stream_with_comment_header(header: Vec<u8>) -> Cursor<Vec<u8>> {
+        let mut output = Cursor::new(vec![]);
+        let mut writer = PacketWriter::new(&mut output);
+        writer
+            .write_packet(
+                OpusHead::default().to_bytes(),
+                1,
+                PacketWriteEndInfo::EndPage,
+                0,
+            )
+            .unwrap();
+        writer
+            .write_packet(header, 1, PacketWriteEndInfo::EndPage, 0)
+            .unwrap();
+        writer
+            .write_packet(
+                vec![0xfc, 0xff, 0xfe],
+                1,
+                PacketWriteEndInfo::EndStream,
+                960,
+            )
+            .unwrap();
+        output.set_position(0);
+        output
+    }
+
+    #[test]
+    fn test_scan_for_misplaced_comment_header() {
+        let mut output = Cursor::new(vec![]);
+        let mut writer = PacketWriter::new(&mut output);
+        let packets = [
+            OpusHead::default().to_bytes(),
+            vec![0xfc, 0xff, 0xfe],
+            comment_header("opusmeta", &[b"TITLE=Silence"]),
+        ];
+        for packet in packets {
+            writer
+                .write_packet(packet, 1, PacketWriteEndInfo::EndPage, 0)
+                .unwrap();
+        }
+        let data = output.into_inner();
+
+        let options = ReadOptions {
+            scan_for_tags: true,
+            ..Default::default()
+        };
+        let tag = Tag::read_from_opts(Cursor::new(&data), &options).unwrap();
+        assert_eq!(tag.get_vendor(), "opusmeta");
+        assert_eq!(tag.get_one(&"title".into()).unwrap(), "Silence");
+
+        assert!(Tag::read_from(Cursor::new(&data)).is_err());
+
+        // the scan gives up when the stream ends
+        let data = stream_with_comment_header(vec![0xfc, 0xff, 0xfe]).into_inner();
+        let result = Tag::read_from_opts(Cursor::new(&data), &options);
+        assert!(matches!(result, Err(Error::MissingPacket)));
+    }
+
     #[test]
     fn test_remove_image_with_no_matching_type() {
         // File contains exactly one image with CoverFront type.
@@ -515,4 +2449,2291 @@ mod tests {
         let remove_result = tag.remove_picture_type(PictureType::CoverFront);
         assert!(matches!(remove_result, Ok(None)));
     }
+
+    #[test]
+    fn test_write_keeps_channel_mapping() {
+        let head = OpusHead {
+            channel_count: 6,
+            channel_mapping_family: 1,
+            channel_mapping: Some(ChannelMapping {
+                stream_count: 4,
+                coupled_count: 2,
+                mapping: vec![0, 4, 1, 2, 3, 5],
+            }),
+            ..OpusHead::default()
+        };
+        let packets = std::iter::repeat_n(vec![0xfc, 0xff, 0xfe], 10);
+        let mut file = Cursor::new(vec![]);
+        Tag::default()
+            .build_stream(&head, packets, &mut file)
+            .unwrap();
+
+        file.set_position(0);
+        let mut tag = Tag::read_from(&mut file).unwrap();
+        tag.add_one("title".into(), "Surround".into());
+        file.set_position(0);
+        tag.write_to(&mut file).unwrap();
+
+        file.set_position(0);
+        assert_eq!(OpusHead::read_from(&mut file).unwrap(), head);
+    }
+
+    #[test]
+    fn test_build_stream() {
+        let mut tag = Tag::new("opusmeta".into(), vec![]);
+        tag.add_one("title".into(), "Silence".into());
+        let head = OpusHead {
+            pre_skip: 312,
+            ..OpusHead::default()
+        };
+        // 20ms CELT stereo silence
+        let packets = std::iter::repeat_n(vec![0xfc, 0xff, 0xfe], 100);
+
+        let mut output = Cursor::new(vec![]);
+        tag.build_stream(&head, packets, &mut output)
+            .expect("Failed to build stream");
+
+        output.set_position(0);
+        let read_tag = Tag::read_from(&mut output).expect("Failed to read tags back");
+        assert_eq!(read_tag.get_vendor(), "opusmeta");
+        assert_eq!(
+            read_tag.get_one(&"title".into()).map(String::as_str),
+            Some("Silence")
+        );
+
+        output.set_position(0);
+        let mut reader = PacketReader::new(output);
+        let first_packet = reader.read_packet().unwrap().unwrap();
+        assert_eq!(OpusHead::from_bytes(&first_packet.data).unwrap(), head);
+
+        let mut last_packet = None;
+        while let Some(packet) = reader.read_packet().unwrap() {
+            last_packet = Some(packet);
+        }
+        let last_packet = last_packet.unwrap();
+        assert!(last_packet.last_in_stream());
+        assert_eq!(last_packet.absgp_page(), 100 * 960);
+    }
+
+    #[test]
+    fn test_sorted_output_is_deterministic() {
+        let pairs = [
+            ("artist", "B"),
+            ("title", "Silence"),
+            ("artist", "A"),
+            ("album", "Nothing"),
+            ("genre", "Ambient"),
+        ];
+
+        let mut first = Tag::new("opusmeta".into(), vec![]);
+        for (tag, value) in pairs {
+            first.add_one(tag.into(), value.into());
+        }
+        let mut second = Tag::new("opusmeta".into(), vec![]);
+        for (tag, value) in pairs.into_iter().rev() {
+            second.add_one(tag.into(), value.into());
+        }
+
+        let options = WriteOptions {
+            sort: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            first.to_packet_data(&options).unwrap(),
+            second.to_packet_data(&options).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_padding() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let tag = Tag::read_from(Cursor::new(&data)).unwrap();
+
+        let write = |padding| {
+            let mut file = Cursor::new(data.clone());
+            let options = WriteOptions {
+                padding,
+                ..Default::default()
+            };
+            tag.write_to_opts(&mut file, &options).unwrap();
+            file.into_inner()
+        };
+        let header_len = |data: Vec<u8>| {
+            let mut reader = PacketReader::new(Cursor::new(data));
+            read_opus_headers(&mut reader, None).unwrap().1.data.len()
+        };
+
+        let unpadded = write(0);
+        let padded = write(1000);
+        assert_eq!(header_len(padded.clone()), header_len(unpadded) + 1000);
+        assert_eq!(Tag::read_from(Cursor::new(padded)).unwrap(), tag);
+    }
+
+    #[test]
+    fn test_picture_placement() {
+        let picture = format!("{PICTURE_BLOCK_TAG}=AAAA");
+        let comments: Vec<&[u8]> = vec![
+            b"artist=A",
+            picture.as_bytes(),
+            b"title=Silence",
+            b"year=2024",
+            b"METADATA_BLOCK_PICTURE=AAAA",
+        ];
+        let header = comment_header("opusmeta", &comments);
+        let tag = Tag::read_from(stream_with_comment_header(header)).unwrap();
+
+        let placed = |picture_placement| {
+            let options = WriteOptions {
+                picture_placement,
+                ..Default::default()
+            };
+            tag.entries(&options)
+                .into_iter()
+                .map(|(tag, _)| tag.eq_ignore_ascii_case(PICTURE_BLOCK_TAG))
+                .collect::<Vec<bool>>()
+        };
+        assert_eq!(
+            placed(PicturePlacement::Last),
+            [false, false, false, true, true]
+        );
+        assert_eq!(
+            placed(PicturePlacement::First),
+            [true, true, false, false, false]
+        );
+    }
+
+    #[test]
+    fn test_duration() {
+        let file = File::open("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let duration = Tag::duration(file).unwrap();
+        // 48312 samples, minus a pre-skip of 312
+        assert_eq!(duration, Duration::from_secs(1));
+
+        let head = OpusHead {
+            pre_skip: 312,
+            ..OpusHead::default()
+        };
+        let packets = std::iter::repeat_n(vec![0xfc, 0xff, 0xfe], 75);
+        let mut file = Cursor::new(vec![]);
+        Tag::default()
+            .build_stream(&head, packets, &mut file)
+            .unwrap();
+        let duration = Tag::duration(Cursor::new(file.into_inner())).unwrap();
+        assert!(duration.abs_diff(Duration::from_millis(1493)) < Duration::from_millis(1));
+
+        // the opus stream doesn't have to be the first logical stream
+        let header = comment_header("vendor", &[]);
+        let duration = Tag::duration(stream_with_skeleton(header)).unwrap();
+        assert_eq!(duration, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_stream_stats() {
+        let file = File::open("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let stats = Tag::stream_stats(file).expect("Failed to walk stream");
+
+        // OpusHead, OpusTags, 50 audio packets on the third page and 1 on the last
+        assert_eq!(stats.pages, 4);
+        assert_eq!(stats.packets, 53);
+        assert_eq!(stats.comment_header_page, 1);
+    }
+
+    #[test]
+    fn test_read_empty_value() {
+        let header = comment_header("opusmeta", &[b"DESCRIPTION="]);
+        let tag = Tag::read_from(stream_with_comment_header(header)).unwrap();
+        assert_eq!(
+            tag.get_one(&"description".into()).map(String::as_str),
+            Some("")
+        );
+    }
+
+    #[test]
+    fn test_read_bare_key() {
+        let header = comment_header("opusmeta", &[b"COMPILATION", b"TITLE=Silence"]);
+        let options = ReadOptions {
+            allow_bare_keys: true,
+            ..Default::default()
+        };
+        let tag = Tag::read_from_opts(stream_with_comment_header(header), &options).unwrap();
+        assert_eq!(
+            tag.get_one(&"compilation".into()).map(String::as_str),
+            Some("")
+        );
+        assert_eq!(
+            tag.get_one(&"title".into()).map(String::as_str),
+            Some("Silence")
+        );
+    }
+
+    #[test]
+    fn test_read_trimmed() {
+        let header = comment_header("opusmeta", &[b"ALBUM = X", b" TITLE=Silence\r"]);
+        let tag = Tag::read_from(stream_with_comment_header(header.clone())).unwrap();
+        assert_eq!(tag.get_one(&"album ".into()).unwrap(), " X");
+        assert_eq!(tag.get_one(&" title".into()).unwrap(), "Silence\r");
+
+        let options = ReadOptions {
+            trim_key_whitespace: true,
+            ..Default::default()
+        };
+        let tag =
+            Tag::read_from_opts(stream_with_comment_header(header.clone()), &options).unwrap();
+        assert_eq!(tag.get_one(&"album".into()).unwrap(), "X");
+        assert_eq!(tag.get_one(&"title".into()).unwrap(), "Silence\r");
+
+        let options = ReadOptions {
+            trim_key_whitespace: true,
+            trim_trailing_cr: true,
+            ..Default::default()
+        };
+        let tag = Tag::read_from_opts(stream_with_comment_header(header), &options).unwrap();
+        assert_eq!(tag.get_one(&"title".into()).unwrap(), "Silence");
+    }
+
+    #[test]
+    fn test_read_max_pictures() {
+        let picture = {
+            let mut picture = Picture::new();
+            picture.mime_type = "image/png".into();
+            picture.data = vec![0x42; 16];
+            format!("METADATA_BLOCK_PICTURE={}", picture.to_base64().unwrap())
+        };
+        let mut comments = vec![picture.as_bytes(); 1000];
+        comments.push(b"TITLE=Silence");
+        let header = comment_header("opusmeta", &comments);
+
+        let options = ReadOptions {
+            max_pictures: Some(3),
+            ..Default::default()
+        };
+        let tag =
+            Tag::read_from_opts(stream_with_comment_header(header.clone()), &options).unwrap();
+        assert_eq!(tag.picture_entries().len(), 3);
+        assert_eq!(
+            tag.get_one(&"title".into()).map(String::as_str),
+            Some("Silence")
+        );
+
+        let options = ReadOptions {
+            max_pictures: Some(3),
+            reject_excess_pictures: true,
+            ..Default::default()
+        };
+        let result = Tag::read_from_opts(stream_with_comment_header(header.clone()), &options);
+        assert!(matches!(result, Err(Error::TooManyPictures { limit: 3 })));
+
+        let tag = Tag::read_from(stream_with_comment_header(header)).unwrap();
+        assert_eq!(tag.picture_entries().len(), 1000);
+    }
+
+    #[test]
+    fn test_reject_webm() {
+        // an EBML header declaring the webm doctype
+        let mut data = vec![0x1a, 0x45, 0xdf, 0xa3, 0x9f, 0x42, 0x82, 0x84];
+        data.extend_from_slice(b"webm");
+        data.resize(64, 0);
+
+        assert!(matches!(
+            Tag::read_from(Cursor::new(&data)),
+            Err(Error::NotOggContainer)
+        ));
+        let tag = Tag::new("opusmeta".into(), vec![]);
+        assert!(matches!(
+            tag.write_to(Cursor::new(data.clone())),
+            Err(Error::NotOggContainer)
+        ));
+    }
+
+    #[test]
+    fn test_comment_header_sharing_page_with_audio() {
+        // the comment header and the first 25 audio packets are on the second page
+        let shared =
+            std::fs::read("testfiles/silence_shared_page.opus").expect("Failed to open testfile");
+        let original =
+            std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let mut tag = Tag::read_from(Cursor::new(&shared)).unwrap();
+        assert_eq!(tag, Tag::read_from(Cursor::new(&original)).unwrap());
+        assert_eq!(tag.pictures().len(), 1);
+
+        tag.remove_entries(&PICTURE_BLOCK_TAG.into());
+        let mut output = Cursor::new(shared.clone());
+        tag.write_to(&mut output).unwrap();
+        output.set_position(0);
+        assert_eq!(Tag::read_from(&mut output).unwrap(), tag);
+
+        let audio_packets = |data: &[u8]| {
+            let mut reader = PacketReader::new(Cursor::new(data));
+            let mut packets = vec![];
+            while let Some(packet) = reader.read_packet().unwrap() {
+                packets.push(packet.data);
+            }
+            packets.split_off(2)
+        };
+        assert_eq!(audio_packets(output.get_ref()), audio_packets(&shared));
+
+        // an audio packet starting on the last page of the comment header and continuing on the
+        // next page isn't overwritten by the in-place fast path
+        let mut data = Cursor::new(vec![]);
+        let mut writer = PacketWriter::new(&mut data);
+        let header = comment_header("opusmeta", &[b"TITLE=Silence", b"ARTIST=A"]);
+        let id_header = OpusHead::default().to_bytes();
+        writer
+            .write_packet(id_header, 1, PacketWriteEndInfo::EndPage, 0)
+            .unwrap();
+        writer
+            .write_packet(header, 1, PacketWriteEndInfo::NormalPacket, 0)
+            .unwrap();
+        let audio = vec![0x42; 70_000];
+        writer
+            .write_packet(audio.clone(), 1, PacketWriteEndInfo::EndStream, 960)
+            .unwrap();
+
+        let tag = Tag::new("opusmeta".into(), vec![("TITLE".into(), "Silence".into())]);
+        data.set_position(0);
+        tag.write_to(&mut data).unwrap();
+        assert_eq!(audio_packets(data.get_ref()), [audio]);
+    }
+
+    #[test]
+    fn test_read_header_only() {
+        /// Panics if anything past the given length is read.
+        struct Limited(Cursor<Vec<u8>>, u64);
+
+        impl Read for Limited {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let read = self.0.read(buf)?;
+                assert!(self.0.position() <= self.1, "read past the comment header");
+                Ok(read)
+            }
+        }
+
+        impl Seek for Limited {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let expected = Tag::read_from(Cursor::new(&data)).unwrap();
+        // the comment header ends with the second page, at byte 348
+        let header_end = 348;
+
+        let limited = Limited(Cursor::new(data.clone()), header_end);
+        assert_eq!(Tag::read_header_only(limited).unwrap(), expected);
+
+        let truncated = &data[..usize::try_from(header_end).unwrap()];
+        assert_eq!(
+            Tag::read_header_only(Cursor::new(truncated)).unwrap(),
+            expected
+        );
+        assert_eq!(Tag::read_from(Cursor::new(truncated)).unwrap(), expected);
+
+        // cut off in the middle of the comment header page, or after the identification header
+        for len in [200, 47] {
+            assert!(matches!(
+                Tag::read_header_only(Cursor::new(&data[..len])),
+                Err(Error::MissingPacket)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_read_id3v2_prefixed() {
+        let mut id3 = b"ID3\x04\0\0".to_vec();
+        // synchsafe size of 300 bytes
+        id3.extend_from_slice(&[0, 0, 0x02, 0x2c]);
+        id3.resize(10 + 300, 0);
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let prefixed = [id3, data.clone()].concat();
+
+        let tag = Tag::read_from(Cursor::new(&prefixed)).unwrap();
+        assert_eq!(tag, Tag::read_from(Cursor::new(&data)).unwrap());
+        assert_eq!(
+            Tag::read_vendor(Cursor::new(&prefixed)).unwrap(),
+            "Lavf61.7.100"
+        );
+        assert!(is_opus(Cursor::new(&prefixed)).unwrap());
+        assert_eq!(
+            OpusHead::read_from(Cursor::new(&prefixed))
+                .unwrap()
+                .pre_skip,
+            312
+        );
+    }
+
+    #[test]
+    fn test_read_bare_key_strict() {
+        let header = comment_header("opusmeta", &[b"COMPILATION"]);
+        let result = Tag::read_from(stream_with_comment_header(header));
+        assert!(matches!(result, Err(Error::MalformedComment(c)) if c == "COMPILATION"));
+    }
+
+    #[test]
+    fn test_normalize_track_fields_combined() {
+        let mut tag: Tag = [
+            ("TRACKNUMBER", "3"),
+            ("TRACKTOTAL", "12"),
+            ("DISCNUMBER", "1"),
+            ("TRACKNUMBER", "4"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        tag.normalize_track_fields(TrackStyle::Combined);
+
+        assert_eq!(tag.values("tracknumber"), ["3/12", "4"]);
+        assert!(tag.get(&"tracktotal".into()).is_none());
+        // no total to combine with
+        assert_eq!(tag.values("discnumber"), ["1"]);
+    }
+
+    #[test]
+    fn test_normalize_track_fields_split() {
+        let mut tag: Tag = [
+            ("TRACKNUMBER", " 3/12"),
+            ("DISCNUMBER", "2/"),
+            ("DISCTOTAL", "3"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        tag.normalize_track_fields(TrackStyle::Split);
+
+        assert_eq!(tag.values("tracknumber"), ["3"]);
+        assert_eq!(tag.values("tracktotal"), ["12"]);
+        assert_eq!(tag.values("discnumber"), ["2"]);
+        assert_eq!(tag.values("disctotal"), ["3"]);
+
+        // splitting again changes nothing
+        let before = tag.clone();
+        tag.normalize_track_fields(TrackStyle::Split);
+        assert_eq!(tag, before);
+    }
+
+    #[test]
+    fn test_normalize_track_fields_non_numeric() {
+        let mut tag: Tag = [
+            ("TRACKNUMBER", "A/B"),
+            ("DISCNUMBER", "one"),
+            ("DISCTOTAL", "2"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        let before = tag.clone();
+        tag.normalize_track_fields(TrackStyle::Split);
+        assert_eq!(tag, before);
+        tag.normalize_track_fields(TrackStyle::Combined);
+        assert_eq!(tag, before);
+    }
+
+    #[test]
+    fn test_pictures_iter() {
+        let header = comment_header("opusmeta", &[b"TITLE=Silence"]);
+        let tag = Tag::read_from(stream_with_comment_header(header)).unwrap();
+        assert!(tag.iter_pictures().is_none());
+        assert_eq!(tag.pictures_iter().count(), 0);
+
+        let tag = Tag::read_from_path("testfiles/silence_cover.opus").unwrap();
+        let pictures: Vec<Picture> = tag.pictures_iter().map(Result::unwrap).collect();
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].picture_type, PictureType::CoverFront);
+    }
+
+    #[test]
+    fn test_remove_picture_at() {
+        // several pictures of the same type, which add_picture can't produce
+        let pictures = (0..3).map(|index| {
+            let mut picture = Picture::new();
+            picture.picture_type = PictureType::Artist;
+            picture.mime_type = "image/png".into();
+            picture.description = index.to_string();
+            picture.data = vec![0x42; 16];
+            (PICTURE_BLOCK_TAG.into(), picture.to_base64().unwrap())
+        });
+        let mut tag = Tag::new("opusmeta".into(), pictures.collect());
+
+        assert!(tag.remove_picture_at(3).is_none());
+        let removed = tag.remove_picture_at(1).unwrap().unwrap();
+        assert_eq!(removed.picture_type, PictureType::Artist);
+        assert_eq!(removed.description, "1");
+
+        // the last picture moved down to index 1
+        let descriptions: Vec<String> = tag.pictures().into_iter().map(|p| p.description).collect();
+        assert_eq!(descriptions, ["0", "2"]);
+        assert_eq!(tag.remove_picture_at(1).unwrap().unwrap().description, "2");
+        assert_eq!(tag.remove_picture_at(0).unwrap().unwrap().description, "0");
+        assert!(tag.remove_picture_at(0).is_none());
+        assert!(!tag.has_pictures());
+    }
+
+    #[test]
+    fn test_keep_only_front_cover() {
+        let picture = |picture_type| {
+            let mut picture = Picture::new();
+            picture.picture_type = picture_type;
+            picture.mime_type = "image/png".into();
+            picture.data = vec![0x42; 16];
+            (PICTURE_BLOCK_TAG.into(), picture.to_base64().unwrap())
+        };
+        let mut tag = Tag::new(
+            "opusmeta".into(),
+            vec![
+                picture(PictureType::Artist),
+                ("TITLE".into(), "Silence".into()),
+                picture(PictureType::CoverFront),
+                picture(PictureType::CoverBack),
+            ],
+        );
+        tag.keep_only_front_cover().unwrap();
+        let pictures = tag.pictures();
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].picture_type, PictureType::CoverFront);
+        assert_eq!(tag.get_one(&"title".into()).unwrap(), "Silence");
+
+        // without a front cover, the first picture is kept
+        let mut tag = Tag::new(
+            "opusmeta".into(),
+            vec![
+                picture(PictureType::Artist),
+                picture(PictureType::CoverBack),
+            ],
+        );
+        tag.keep_only_front_cover().unwrap();
+        let pictures = tag.pictures();
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].picture_type, PictureType::Artist);
+
+        let mut tag = Tag::new(
+            "opusmeta".into(),
+            vec![
+                picture(PictureType::Artist),
+                (PICTURE_BLOCK_TAG.into(), "not a picture".into()),
+            ],
+        );
+        assert!(tag.keep_only_front_cover().is_err());
+        assert_eq!(tag.picture_entries().len(), 2);
+    }
+
+    #[test]
+    fn test_keep_only_front_cover_without_pictures() {
+        // removing the only picture leaves the picture key without any values
+        let mut tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        tag.remove_picture_type(PictureType::CoverFront).unwrap();
+        tag.keep_only_front_cover().unwrap();
+        assert!(tag.pictures().is_empty());
+
+        let mut tag = Tag::new("opusmeta".into(), vec![]);
+        tag.keep_only_front_cover().unwrap();
+        assert!(!tag.has_pictures());
+    }
+
+    #[test]
+    fn test_set_comments() {
+        let mut tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let comments = HashMap::from([
+            ("Artist".to_string(), vec!["B".to_string()]),
+            ("ARTIST".to_string(), vec!["A".to_string()]),
+            ("title".to_string(), vec!["Silence".to_string()]),
+        ]);
+        tag.set_comments(comments);
+
+        assert_eq!(tag.artists(), ["A", "B"]);
+        assert_eq!(
+            tag.get_one(&"title".into()).map(String::as_str),
+            Some("Silence")
+        );
+        // the old comments are gone, but the picture is kept
+        assert_eq!(tag.encoder(), None);
+        assert_eq!(tag.pictures().len(), 1);
+
+        let picture_block = HashMap::from([(PICTURE_BLOCK_TAG.to_string(), vec![])]);
+        tag.set_comments(picture_block);
+        assert!(tag.pictures().is_empty());
+        assert!(tag.artists().is_empty());
+    }
+
+    #[test]
+    fn test_set_comments_forgets_layout() {
+        let header = comment_header("opusmeta", &[b"TITLE=Old", b"ARTIST=Old"]);
+        let mut file = stream_with_comment_header(header);
+        let mut tag = Tag::read_from(&mut file).unwrap();
+        tag.set_comments(HashMap::from([
+            ("title".to_string(), vec!["Silence".to_string()]),
+            ("artist".to_string(), vec!["A".to_string()]),
+        ]));
+        file.set_position(0);
+        tag.write_to(&mut file).unwrap();
+
+        file.set_position(0);
+        let mut reader = PacketReader::new(file);
+        let (_, header_packet) = read_opus_headers(&mut reader, None).unwrap();
+        assert_eq!(
+            header_packet.data,
+            comment_header("opusmeta", &[b"artist=A", b"title=Silence"])
+        );
+    }
+
+    #[test]
+    fn test_from_comment_header() {
+        let mut tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        tag.add_artist("A".into());
+        tag.add_artist("B".into());
+        let packet = tag.to_packet_data(&WriteOptions::default()).unwrap();
+        assert_eq!(Tag::from_comment_header(&packet).unwrap(), tag);
+
+        assert!(matches!(
+            Tag::from_comment_header(&OpusHead::default().to_bytes()),
+            Err(Error::NotOpus)
+        ));
+        assert!(matches!(
+            Tag::from_comment_header(&packet[..20]),
+            Err(Error::TruncatedVendor)
+        ));
+    }
+
+    #[test]
+    fn test_get_one_parsed() {
+        let tag: Tag = [("TRACKNUMBER", "3"), ("BPM", "fast"), ("BPM", "120")]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        assert_eq!(
+            tag.get_one_parsed::<u32>(&"tracknumber".into()),
+            Some(Ok(3))
+        );
+        assert!(tag.get_one_parsed::<u32>(&"discnumber".into()).is_none());
+        // only the first entry is parsed
+        assert!(matches!(
+            tag.get_one_parsed::<u32>(&"bpm".into()),
+            Some(Err(_))
+        ));
+    }
+
+    #[test]
+    fn test_add_if_absent() {
+        let mut tag = Tag::new("opusmeta".into(), vec![("ARTIST".into(), "A".into())]);
+
+        assert!(!tag.add_if_absent("artist".into(), "Fallback".into()));
+        assert_eq!(tag.artists(), ["A"]);
+
+        assert!(tag.add_if_absent("genre".into(), "Ambient".into()));
+        assert_eq!(tag.genres(), ["Ambient"]);
+        assert!(!tag.add_if_absent("genre".into(), "Drone".into()));
+        assert_eq!(tag.genres(), ["Ambient"]);
+    }
+
+    #[test]
+    fn test_file_has_picture() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        assert!(Tag::file_has_picture(Cursor::new(&data)).unwrap());
+
+        let mut tag = Tag::read_from(Cursor::new(&data)).unwrap();
+        tag.remove_entries(&PICTURE_BLOCK_TAG.into());
+        let mut output = Cursor::new(data);
+        tag.write_to(&mut output).unwrap();
+        output.set_position(0);
+        assert!(!Tag::file_has_picture(output).unwrap());
+    }
+
+    #[test]
+    fn test_header_size_delta() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let original = Tag::read_from(Cursor::new(&data)).unwrap();
+        let original_len = original.encoded_len();
+        assert_eq!(
+            original_len,
+            original
+                .to_packet_data(&WriteOptions::default())
+                .unwrap()
+                .len()
+        );
+        assert_eq!(original.header_size_delta(Cursor::new(&data)).unwrap(), 0);
+
+        let mut tag = original;
+        tag.add_one("title".into(), "Silence".into());
+        let delta = tag.header_size_delta(Cursor::new(&data)).unwrap();
+        assert_eq!(
+            delta,
+            i64::try_from(tag.encoded_len() - original_len).unwrap()
+        );
+        assert_eq!(delta, 4 + 13);
+
+        tag.remove_entries(&PICTURE_BLOCK_TAG.into());
+        assert!(tag.header_size_delta(Cursor::new(&data)).unwrap() < 0);
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut tag = Tag::new("opusmeta".into(), vec![("ARTIST".into(), "A".into())]);
+
+        tag.entry("artist".into()).or_default().push("B".into());
+        tag.entry("genre".into())
+            .or_default()
+            .push("Ambient".into());
+        assert_eq!(tag.artists(), ["A", "B"]);
+        assert_eq!(tag.genres(), ["Ambient"]);
+
+        let genres = tag
+            .entry("genre".into())
+            .and_modify(Vec::clear)
+            .or_insert(vec!["Drone".into()]);
+        assert!(genres.is_empty());
+
+        let entry = tag.entry("TITLE".into());
+        assert_eq!(entry.key(), "title");
+        assert_eq!(entry.or_insert(vec!["Silence".into()]), &["Silence"]);
+        assert_eq!(tag.get_one(&"title".into()).unwrap(), "Silence");
+    }
+
+    #[test]
+    fn test_reject_control_chars() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let options = WriteOptions {
+            reject_control_chars: true,
+            ..Default::default()
+        };
+
+        let mut tag = Tag::read_from(Cursor::new(&data)).unwrap();
+        tag.add_one("lyrics".into(), "first line\r\n\tsecond line".into());
+        tag.write_to_opts(Cursor::new(data.clone()), &options)
+            .unwrap();
+
+        tag.add_one("title".into(), "Sil\0ence".into());
+        let result = tag.write_to_opts(Cursor::new(data.clone()), &options);
+        assert!(matches!(result, Err(Error::ControlCharacter { key }) if key == "title"));
+
+        tag.write_to(Cursor::new(data)).unwrap();
+    }
+
+    #[test]
+    fn test_classical_fields() {
+        let mut tag = Tag::default();
+        assert_eq!(tag.work(), None);
+        assert_eq!(tag.movement(), None);
+
+        tag.set_work("Symphony No. 9 in D minor, Op. 125");
+        tag.set_movement(4);
+        tag.set_movement_name("Presto");
+        tag.set_movement_total(4);
+        tag.set_grouping("Beethoven Symphonies");
+
+        let mut file = Cursor::new(std::fs::read("testfiles/silence_cover.opus").unwrap());
+        tag.write_to(&mut file).unwrap();
+        file.rewind().unwrap();
+        let tag = Tag::read_from(file).unwrap();
+
+        assert_eq!(tag.work(), Some("Symphony No. 9 in D minor, Op. 125"));
+        assert_eq!(tag.movement(), Some(4));
+        assert_eq!(tag.movement_name(), Some("Presto"));
+        assert_eq!(tag.movement_total(), Some(4));
+        assert_eq!(tag.grouping(), Some("Beethoven Symphonies"));
+        assert_eq!(tag.get_one(&"movementname".into()).unwrap(), "Presto");
+
+        let mut tag = Tag::default();
+        tag.add_one("movement".into(), "IV".into());
+        assert_eq!(tag.movement(), None);
+    }
+
+    #[test]
+    fn test_extract_pictures_to_dir() {
+        let file = File::open("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let front = Tag::read_from(file)
+            .unwrap()
+            .get_picture_type(PictureType::CoverFront)
+            .unwrap();
+        let back = Picture {
+            picture_type: PictureType::CoverBack,
+            mime_type: "image/jpeg".into(),
+            data: vec![0xff, 0xd8, 0xff, 0xe0],
+            ..Picture::new()
+        };
+        let other = Picture {
+            mime_type: "image/x-unknown".into(),
+            data: vec![1, 2, 3],
+            ..Picture::new()
+        };
+        let comments = [&front, &back, &other, &other]
+            .into_iter()
+            .map(|picture| (PICTURE_BLOCK_TAG.into(), picture.to_base64().unwrap()))
+            .chain([(PICTURE_BLOCK_TAG.into(), "not base64".into())])
+            .collect();
+        let tag = Tag::new(String::new(), comments);
+
+        let dir = std::env::temp_dir().join(format!("opusmeta-extract-{}", std::process::id()));
+        let paths = tag.extract_pictures_to_dir(&dir).unwrap();
+        assert_eq!(
+            paths,
+            [
+                "cover_front.png",
+                "cover_back.jpg",
+                "other.bin",
+                "other_2.bin"
+            ]
+            .map(|name| dir.join(name))
+        );
+        assert_eq!(std::fs::read(&paths[0]).unwrap(), front.data);
+        assert_eq!(std::fs::read(&paths[1]).unwrap(), back.data);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 4);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sort_keys_by() {
+        let packet = comment_header(
+            "opusmeta",
+            &[
+                b"DATE=2024",
+                b"ALBUM=Quiet",
+                b"ARTIST=A",
+                b"GENRE=Ambient",
+                b"TITLE=Silence",
+                b"ARTIST=B",
+            ],
+        );
+        let mut tag = Tag::from_comment_header(&packet).unwrap();
+        tag.add_one("comment".into(), "added".into());
+
+        let priority = |key: &str| {
+            ["title", "artist", "album"]
+                .iter()
+                .position(|&first| first == key)
+                .unwrap_or(usize::MAX)
+        };
+        tag.sort_keys_by(|a, b| priority(a).cmp(&priority(b)).then(a.cmp(b)));
+        assert_eq!(
+            tag.comment_lines(),
+            [
+                "TITLE=Silence",
+                "ARTIST=A",
+                "ARTIST=B",
+                "ALBUM=Quiet",
+                "comment=added",
+                "DATE=2024",
+                "GENRE=Ambient",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replaygain() {
+        let packet = comment_header(
+            "opusmeta",
+            &[
+                b"REPLAYGAIN_TRACK_GAIN=-6.35 dB",
+                b"REPLAYGAIN_ALBUM_GAIN=-7.1",
+                b"R128_TRACK_GAIN=-2906",
+            ],
+        );
+        let mut tag = Tag::from_comment_header(&packet).unwrap();
+        assert_eq!(tag.replaygain_track_gain(), Some(-6.35));
+        assert_eq!(tag.replaygain_album_gain(), Some(-7.1));
+        assert_eq!(tag.r128_track_gain(), Some(-2906));
+        assert_eq!(tag.r128_album_gain(), None);
+
+        let album_gain = replaygain_to_r128(tag.replaygain_album_gain().unwrap());
+        tag.set_r128_album_gain(album_gain);
+        assert_eq!(tag.get_one(&"r128_album_gain".into()).unwrap(), "-3098");
+
+        tag.set_replaygain_track_gain(r128_to_replaygain(tag.r128_track_gain().unwrap()));
+        assert_eq!(
+            tag.get_one(&"replaygain_track_gain".into()).unwrap(),
+            "-6.35 dB"
+        );
+    }
+
+    #[test]
+    fn test_strip_personal() {
+        let file = File::open("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let mut tag = Tag::read_from(file).unwrap();
+        for (key, value) in [
+            ("title", "Silence"),
+            ("artist", "Nobody"),
+            ("album", "Quiet"),
+            ("comment", "ripped at home"),
+            ("contact", "me@example.com"),
+            ("location", "Berlin"),
+        ] {
+            tag.add_one(key.into(), value.into());
+        }
+
+        tag.strip_personal();
+        assert!(!tag.has_pictures());
+        let keys: Vec<&str> = tag.keys().collect();
+        assert_eq!(keys, ["encoder", "title", "artist", "album"]);
+
+        tag.strip_keys(&["ENCODER", "Album", "unknown"]);
+        let keys: Vec<&str> = tag.keys().collect();
+        assert_eq!(keys, ["title", "artist"]);
+    }
+
+    #[test]
+    fn test_apply_standard() {
+        let mut tag = Tag::new(
+            "opusmeta".into(),
+            vec![
+                ("TITLE".into(), "Old".into()),
+                ("ARTIST".into(), "A".into()),
+                ("ARTIST".into(), "B".into()),
+                ("DATE".into(), "2023".into()),
+                ("GENRE".into(), "Ambient".into()),
+            ],
+        );
+        tag.apply_standard(&StandardTags {
+            title: Some("Silence".into()),
+            artist: Some("C".into()),
+            track: Some(3),
+            ..Default::default()
+        });
+
+        assert_eq!(tag.get(&"title".into()).unwrap(), &["Silence"]);
+        assert_eq!(tag.artists(), ["C"]);
+        assert_eq!(tag.get(&"tracknumber".into()).unwrap(), &["3"]);
+        // the fields which weren't provided are unchanged
+        assert_eq!(tag.get(&"date".into()).unwrap(), &["2023"]);
+        assert_eq!(tag.get(&"album".into()), None);
+        assert_eq!(tag.genres(), ["Ambient"]);
+    }
+
+    #[test]
+    fn test_prune_empty() {
+        let mut tag = Tag::new(
+            String::new(),
+            vec![
+                ("TITLE".into(), "Silence".into()),
+                ("COMMENT".into(), String::new()),
+                ("ARTIST".into(), String::new()),
+                ("ARTIST".into(), "A".into()),
+            ],
+        );
+        tag.add_many("genre".into(), vec![]);
+
+        tag.prune_empty(false);
+        assert_eq!(
+            tag.keys().collect::<Vec<_>>(),
+            ["title", "comment", "artist"]
+        );
+        assert_eq!(tag.artists(), ["", "A"]);
+
+        tag.prune_empty(true);
+        assert_eq!(tag.keys().collect::<Vec<_>>(), ["title", "artist"]);
+        assert_eq!(tag.artists(), ["A"]);
+        assert_eq!(tag.get_one(&"title".into()).unwrap(), "Silence");
+    }
+
+    #[test]
+    fn test_truncate_values() {
+        let mut tag = Tag::new(
+            String::new(),
+            vec![
+                ("title".into(), "Short".into()),
+                ("comment".into(), "abcd\u{e9}fgh".into()),
+            ],
+        );
+        let picture = Picture {
+            picture_type: PictureType::CoverFront,
+            data: vec![0; 64],
+            ..Picture::new()
+        };
+        tag.add_picture(&picture).unwrap();
+
+        // the limit falls in the middle of the two byte 'é'
+        assert_eq!(tag.truncate_values(5), 1);
+        assert_eq!(tag.get_one(&"comment".into()).unwrap(), "abcd");
+        assert_eq!(tag.get_one(&"title".into()).unwrap(), "Short");
+        assert_eq!(tag.pictures()[0].data, picture.data);
+
+        assert_eq!(tag.truncate_values(5), 0);
+    }
+
+    #[cfg(feature = "gz")]
+    #[test]
+    fn test_read_from_gz() {
+        let file = File::open("testfiles/silence_cover.opus.gz").expect("Failed to open testfile");
+        let tag = Tag::read_from_gz(file).unwrap();
+        let expected = Tag::read_from_path("testfiles/silence_cover.opus").unwrap();
+        assert_eq!(tag, expected);
+
+        let file = File::open("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        assert!(matches!(Tag::read_from_gz(file), Err(Error::DataError(_))));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_read_from_zst() {
+        let file = File::open("testfiles/silence_cover.opus.zst").expect("Failed to open testfile");
+        let tag = Tag::read_from_zst(file).unwrap();
+        let expected = Tag::read_from_path("testfiles/silence_cover.opus").unwrap();
+        assert_eq!(tag, expected);
+    }
+
+    #[test]
+    fn test_had_duplicate_keys() {
+        let file = File::open("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let tag = Tag::read_from(file).unwrap();
+        assert!(!tag.had_duplicate_keys());
+
+        let packet = comment_header(
+            "opusmeta",
+            &[b"TITLE=Silence", b"ALBUM=Quiet", b"TITLE=Silence"],
+        );
+        let tag = Tag::from_comment_header(&packet).unwrap();
+        assert!(tag.had_duplicate_keys());
+        assert_eq!(tag.get(&"title".into()).unwrap(), &["Silence", "Silence"]);
+
+        // several values in a row are ordinary comments
+        let packet = comment_header("opusmeta", &[b"ARTIST=A", b"ARTIST=B", b"TITLE=Silence"]);
+        assert!(
+            !Tag::from_comment_header(&packet)
+                .unwrap()
+                .had_duplicate_keys()
+        );
+
+        let packet = comment_header("opusmeta", &[b"Artist=A", b"ARTIST=B"]);
+        assert!(
+            Tag::from_comment_header(&packet)
+                .unwrap()
+                .had_duplicate_keys()
+        );
+    }
+
+    #[test]
+    fn test_into_comments() {
+        let file = File::open("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let mut tag = Tag::read_from(file).unwrap();
+        tag.add_many("artist".into(), vec!["A".into(), "B".into()]);
+
+        let comments: Vec<(String, String)> = tag.into_comments().collect();
+        assert_eq!(comments.len(), 3);
+        assert_eq!(
+            comments[1..],
+            [
+                ("artist".to_string(), "A".to_string()),
+                ("artist".to_string(), "B".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let pairs = [("title", "Silence"), ("artist", "A"), ("album", "Quiet")];
+        let forward: Tag = pairs
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let backward: Tag = pairs
+            .iter()
+            .rev()
+            .map(|&(key, value)| (key.to_uppercase(), value.to_string()))
+            .collect();
+        assert_eq!(forward.content_hash(), backward.content_hash());
+
+        let mut changed = forward.clone();
+        changed.set_vendor("opusmeta".into());
+        assert_ne!(changed.content_hash(), forward.content_hash());
+
+        // the boundary between key and value is part of the hash
+        let split = |key: &str, value: &str| {
+            Tag::from_iter([(key.to_string(), value.to_string())]).content_hash()
+        };
+        assert_ne!(split("ab", "c"), split("a", "bc"));
+    }
+
+    #[test]
+    fn test_add_picture_with_dimensions() {
+        let file = File::open("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let cover = Tag::read_from(file)
+            .unwrap()
+            .get_picture_type(PictureType::CoverFront)
+            .unwrap();
+        let picture = Picture::read_from(cover.data.as_slice(), None).unwrap();
+        assert_eq!((picture.width, picture.height), (0, 0));
+
+        let mut tag = Tag::default();
+        tag.add_picture_with_dimensions(&picture).unwrap();
+        let stored = Picture::from_base64(&tag.picture_entries()[0]).unwrap();
+        assert!(stored.width > 0 && stored.height > 0);
+        assert_eq!(
+            Some((stored.width, stored.height)),
+            picture.decode_dimensions()
+        );
+
+        // pictures in unsupported formats are added unchanged
+        let picture = Picture {
+            mime_type: "image/x-unknown".into(),
+            data: vec![1, 2, 3],
+            ..Picture::new()
+        };
+        tag.add_picture_with_dimensions(&picture).unwrap();
+        let stored = tag.get_picture_type(PictureType::Other).unwrap();
+        assert_eq!((stored.width, stored.height), (0, 0));
+    }
+
+    #[test]
+    fn test_pictures_with_index() {
+        let picture = |picture_type| Picture {
+            picture_type,
+            mime_type: "image/png".into(),
+            data: vec![1, 2, 3],
+            ..Picture::new()
+        };
+        let entries = vec![
+            picture(PictureType::CoverFront).to_base64().unwrap(),
+            "not base64".to_string(),
+            picture(PictureType::CoverBack).to_base64().unwrap(),
+        ];
+        let mut tag = Tag::default();
+        tag.add_many(PICTURE_BLOCK_TAG.into(), entries);
+
+        let pictures: Vec<_> = tag.pictures_with_index().collect();
+        assert_eq!(pictures.len(), 3);
+        assert!(matches!(&pictures[0], (0, Ok(p)) if p.picture_type == PictureType::CoverFront));
+        assert!(matches!(pictures[1], (1, Err(_))));
+        assert!(matches!(&pictures[2], (2, Ok(p)) if p.picture_type == PictureType::CoverBack));
+
+        let index = tag
+            .pictures_with_index()
+            .find_map(|(index, picture)| {
+                (picture.ok()?.picture_type == PictureType::CoverBack).then_some(index)
+            })
+            .unwrap();
+        tag.remove_picture_at(index).unwrap().unwrap();
+        assert_eq!(tag.picture_entries().len(), 2);
+    }
+
+    #[test]
+    fn test_debug_abbreviates_pictures() {
+        let file = File::open("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let tag = Tag::read_from(file).unwrap();
+        let entry = &tag.picture_entries()[0];
+
+        let debug = format!("{tag:?}");
+        assert!(!debug.contains(entry.as_str()));
+        assert!(debug.contains(&entry[..16]));
+        assert!(debug.contains(&format!("[{} bytes]", entry.len())));
+        assert!(debug.contains("Lavc61.19.101 libopus"));
+
+        let picture = tag.get_picture_type(PictureType::CoverFront).unwrap();
+        let debug = format!("{picture:?}");
+        assert!(debug.contains(&format!("data: [{} bytes]", picture.data.len())));
+        assert!(debug.contains("image/png"));
+    }
+
+    #[test]
+    fn test_move_value() {
+        let mut tag = Tag::default();
+        tag.add_many("artist".into(), vec!["A".into(), "B".into(), "C".into()]);
+
+        assert!(tag.move_value(&"artist".into(), 2, 0));
+        assert_eq!(tag.artists(), ["C", "A", "B"]);
+        assert!(tag.move_value(&"artist".into(), 0, 2));
+        assert_eq!(tag.artists(), ["A", "B", "C"]);
+        assert!(tag.move_value(&"artist".into(), 1, 1));
+        assert_eq!(tag.artists(), ["A", "B", "C"]);
+
+        assert!(!tag.move_value(&"artist".into(), 3, 0));
+        assert!(!tag.move_value(&"artist".into(), 0, 3));
+        assert!(!tag.move_value(&"title".into(), 0, 0));
+        assert_eq!(tag.artists(), ["A", "B", "C"]);
+    }
+
+    #[test]
+    fn test_key_value_counts() {
+        let mut tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        tag.add_many("artist".into(), vec!["A".into(), "B".into(), "C".into()]);
+        tag.add_one("Title".into(), "Silence".into());
+
+        let counts = tag.key_value_counts();
+        assert_eq!(counts, [("encoder", 1), ("artist", 3), ("title", 1)]);
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let tag: Tag = [
+            ("TITLE", "Silence"),
+            ("ARIST", "A"),
+            ("MYAPP_RATING", "5"),
+            ("METADATA_BLOCK_PICTURE", "AAAA"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        let unknown: Vec<&str> = tag.unknown_keys().collect();
+        assert_eq!(unknown, ["arist", "myapp_rating"]);
+    }
+
+    #[test]
+    fn test_get_split() {
+        let mut tag = Tag::default();
+        tag.add_one("artist".into(), "A;B".into());
+        tag.add_one("artist".into(), "C".into());
+        assert_eq!(tag.get_split(&"artist".into(), ';'), ["A", "B", "C"]);
+        assert!(tag.get_split(&"title".into(), ';').is_empty());
+    }
+
+    #[test]
+    fn test_set_joined() {
+        let mut tag = Tag::default();
+        tag.add_many("artist".into(), vec!["A".into(), "B".into()]);
+        let values = tag.get(&"artist".into()).cloned().unwrap();
+        tag.set_joined("artist".into(), &values, '\0');
+        assert_eq!(tag.get(&"artist".into()).unwrap(), &["A\0B"]);
+        assert_eq!(tag.get_split(&"artist".into(), '\0'), values);
+    }
+
+    #[test]
+    fn test_add_picture_checked_empty_data() {
+        let mut tag = Tag::default();
+        let mut picture = Picture::new();
+        picture.mime_type = "image/png".into();
+
+        let result = tag.add_picture_checked(&picture);
+        assert!(matches!(
+            result,
+            Err(Error::PictureError(PictureError::EmptyData))
+        ));
+        assert!(!tag.has_pictures());
+    }
+
+    #[test]
+    fn test_add_picture_checked_empty_mime() {
+        let mut tag = Tag::default();
+        let mut picture = Picture::new();
+        picture.data = vec![1, 2, 3];
+
+        let result = tag.add_picture_checked(&picture);
+        assert!(matches!(
+            result,
+            Err(Error::PictureError(PictureError::EmptyMimeType))
+        ));
+        assert!(!tag.has_pictures());
+    }
+
+    #[test]
+    fn test_iter_comments_sorted() {
+        let mut tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        tag.add_one("title".into(), "Silence".into());
+        tag.add_one("album".into(), "Nothing".into());
+        tag.add_one("artist".into(), "Nobody".into());
+
+        let keys: Vec<&str> = tag.iter_comments_sorted().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["album", "artist", "encoder", "title"]);
+    }
+
+    #[test]
+    fn test_set_output_gain() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let mut file = Cursor::new(data);
+
+        Tag::set_output_gain(&mut file, -256).expect("Failed to set output gain");
+
+        file.set_position(0);
+        let head = OpusHead::read_from(&mut file).unwrap();
+        assert_eq!(head.output_gain, -256);
+        assert_eq!(head.pre_skip, 312);
+
+        // the rest of the stream is untouched and still readable
+        file.set_position(0);
+        let tag = Tag::read_from(&mut file).unwrap();
+        assert!(tag.has_pictures());
+    }
+
+    #[test]
+    fn test_set_output_gain_after_skeleton_stream() {
+        let header = comment_header("opusmeta", &[b"TITLE=Silence"]);
+        let original = stream_with_skeleton(header).into_inner();
+        let mut file = Cursor::new(original.clone());
+
+        Tag::set_output_gain(&mut file, 512).expect("Failed to set output gain");
+
+        // the skeleton page in front of the opus stream is left alone
+        let data = file.into_inner();
+        let segments = usize::from(original[26]);
+        let skeleton_len = 27
+            + segments
+            + original[27..27 + segments]
+                .iter()
+                .map(|&l| usize::from(l))
+                .sum::<usize>();
+        assert!(data[skeleton_len..].starts_with(b"OggS"));
+        assert_eq!(data[..skeleton_len], original[..skeleton_len]);
+
+        let mut reader = PacketReader::new(Cursor::new(&data));
+        let (first_packet, _) = read_opus_headers(&mut reader, None).unwrap();
+        assert_eq!(
+            OpusHead::from_bytes(&first_packet.data)
+                .unwrap()
+                .output_gain,
+            512
+        );
+        let tag = Tag::read_from(Cursor::new(&data)).unwrap();
+        assert_eq!(tag.get_one(&"title".into()).unwrap(), "Silence");
+    }
+
+    #[test]
+    fn test_picture_entries() {
+        let tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        assert_eq!(tag.picture_entries().len(), tag.pictures().len());
+        assert_eq!(tag.picture_entries().len(), 1);
+
+        assert!(Tag::default().picture_entries().is_empty());
+    }
+
+    #[test]
+    fn test_read_after_skeleton_stream() {
+        let header = comment_header("opusmeta", &[b"TITLE=Silence"]);
+        let tag = Tag::read_from(stream_with_skeleton(header)).unwrap();
+        assert_eq!(tag.get_vendor(), "opusmeta");
+        assert_eq!(
+            tag.get_one(&"title".into()).map(String::as_str),
+            Some("Silence")
+        );
+    }
+
+    #[test]
+    fn test_write_preserves_skeleton_stream() {
+        fn packets(data: &[u8], serial: u32) -> Vec<Vec<u8>> {
+            let mut reader = PacketReader::new(Cursor::new(data));
+            let mut packets = vec![];
+            while let Some(packet) = reader.read_packet().unwrap() {
+                if packet.stream_serial() == serial {
+                    packets.push(packet.data);
+                }
+            }
+            packets
+        }
+
+        let header = comment_header("opusmeta", &[b"TITLE=Silence"]);
+        let mut data = stream_with_skeleton(header);
+        let original = data.get_ref().clone();
+
+        let mut tag = Tag::read_from(&mut data).unwrap();
+        tag.add_one("artist".into(), "A".repeat(300));
+        data.set_position(0);
+        tag.write_to(&mut data).unwrap();
+
+        assert_eq!(packets(data.get_ref(), 7), packets(&original, 7));
+        let opus_packets = packets(data.get_ref(), 1);
+        assert_eq!(opus_packets.len(), 3);
+        assert_eq!(opus_packets[2], [0xfc, 0xff, 0xfe]);
+
+        data.set_position(0);
+        assert_eq!(Tag::read_from(&mut data).unwrap(), tag);
+    }
+
+    #[test]
+    fn test_collect_and_extend() {
+        let mut tag: Tag = [("ARTIST", "A"), ("Title", "Silence")]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(tag.get_vendor(), "");
+        assert_eq!(
+            tag.get_one(&"title".into()).map(String::as_str),
+            Some("Silence")
+        );
+
+        tag.extend([("artist".to_string(), "B".to_string())]);
+        assert_eq!(tag.get(&"artist".into()).unwrap(), &["A", "B"]);
+    }
+
+    #[test]
+    fn test_report_casing_collisions() {
+        let header = comment_header(
+            "opusmeta",
+            &[
+                b"Artist=A",
+                b"TITLE=Silence",
+                b"ARTIST=B",
+                b"artist=C",
+                b"title=Again",
+            ],
+        );
+        let (tag, collisions) =
+            Tag::read_from_report_casing(stream_with_comment_header(header)).unwrap();
+        assert_eq!(collisions, ["artist", "title"]);
+        assert_eq!(tag.get(&"artist".into()).unwrap(), &["A", "B", "C"]);
+
+        let header = comment_header("opusmeta", &[b"ARTIST=A", b"ARTIST=B"]);
+        let (_, collisions) =
+            Tag::read_from_report_casing(stream_with_comment_header(header)).unwrap();
+        assert!(collisions.is_empty());
+    }
+
+    #[test]
+    fn test_read_vendor() {
+        let vendor = Tag::read_vendor(File::open("testfiles/silence_cover.opus").unwrap())
+            .expect("Failed to read vendor");
+        let tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        assert_eq!(vendor, tag.get_vendor());
+        assert_eq!(vendor, "Lavf61.7.100");
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        tag.add_many("comment".into(), vec![String::new(), "Keep me".into()]);
+        tag.add_one("description".into(), String::new());
+
+        tag.retain(|_, value| !value.is_empty());
+
+        assert_eq!(tag.get(&"comment".into()).unwrap(), &["Keep me"]);
+        assert!(tag.get(&"description".into()).is_none());
+        assert!(tag.get(&"encoder".into()).is_some());
+        assert!(tag.has_pictures());
+    }
+
+    #[test]
+    fn test_read_from_slice() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let tag = Tag::read_from_slice(&data).expect("Failed to read tags from slice");
+        assert_eq!(tag.get_vendor(), "Lavf61.7.100");
+        assert!(tag.has_pictures());
+    }
+
+    #[test]
+    fn test_roundtrip_bytes() {
+        let fixture =
+            std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        assert!(Tag::roundtrip_bytes(&fixture).unwrap());
+
+        let picture = large_picture_comment("METADATA_BLOCK_PICTURE");
+        let interleaved = comment_header(
+            "opusmeta",
+            &[
+                b"ARTIST=A",
+                b"title=Silence",
+                &picture,
+                b"Artist=B",
+                b"DATE=2024",
+            ],
+        );
+        let mut padded = interleaved.clone();
+        padded.extend_from_slice(&[0; 512]);
+        let mut binary = interleaved.clone();
+        binary.extend_from_slice(b"\x01binary data");
+
+        for header in [interleaved, padded, binary] {
+            let stream = stream_with_comment_header(header).into_inner();
+            assert!(Tag::roundtrip_bytes(&stream).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_write_preserves_comment_order() {
+        let header = comment_header(
+            "opusmeta",
+            &[b"ARTIST=A", b"TITLE=Silence", b"Artist=B", b"DATE=2024"],
+        );
+        let mut tag = Tag::from_comment_header(&header).unwrap();
+        tag.add_one("genre".into(), "Ambient".into());
+        tag.add_one("artist".into(), "C".into());
+        tag.remove_entries(&"title".into());
+        assert_eq!(
+            tag.comment_lines(),
+            [
+                "ARTIST=A",
+                "Artist=B",
+                "DATE=2024",
+                "artist=C",
+                "genre=Ambient"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_preserves_trailing_data() {
+        let mut header = comment_header("opusmeta", &[b"TITLE=Silence"]);
+        header.extend_from_slice(&[0; 100]);
+        let tag = Tag::from_comment_header(&header).unwrap();
+
+        let options = WriteOptions::default();
+        assert_eq!(tag.to_packet_data(&options).unwrap(), header);
+        // padding is only added up to the requested length
+        let options = WriteOptions {
+            padding: 300,
+            ..Default::default()
+        };
+        assert_eq!(
+            tag.to_packet_data(&options).unwrap().len(),
+            header.len() + 200
+        );
+
+        // binary data is never extended
+        let mut header = comment_header("opusmeta", &[b"TITLE=Silence"]);
+        header.extend_from_slice(b"\x01binary data");
+        let tag = Tag::from_comment_header(&header).unwrap();
+        assert_eq!(tag.to_packet_data(&options).unwrap(), header);
+    }
+
+    #[test]
+    fn test_read_trailing_data() {
+        let mut header = comment_header("opusmeta", &[b"TITLE=Silence", b"ARTIST=A"]);
+        header.extend_from_slice(b"\x01binary data");
+        let tag = Tag::read_from(stream_with_comment_header(header)).unwrap();
+        assert_eq!(tag.trailing_data(), b"\x01binary data");
+        assert_eq!(tag.get_one(&"title".into()).unwrap(), "Silence");
+        assert_eq!(tag.artists(), ["A"]);
+
+        let mut output = stream_with_comment_header(comment_header("opusmeta", &[]));
+        tag.write_to(&mut output).unwrap();
+        output.set_position(0);
+        let written = Tag::read_from(output).unwrap();
+        assert_eq!(written, tag);
+        assert_eq!(written.trailing_data(), b"\x01binary data");
+
+        let tag = Tag::new("opusmeta".into(), vec![]);
+        assert!(tag.trailing_data().is_empty());
+    }
+
+    #[test]
+    fn test_comment_lines() {
+        let mut tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        tag.add_many("artist".into(), vec!["A".into(), "B".into()]);
+
+        // the comments read from the file keep their order and case, new ones come last
+        let expected = [
+            "encoder=Lavc61.19.101 libopus".to_string(),
+            format!("METADATA_BLOCK_PICTURE={}", tag.picture_entries()[0]),
+            "artist=A".to_string(),
+            "artist=B".to_string(),
+        ];
+        assert_eq!(tag.comment_lines(), expected);
+        assert!(tag.comment_lines().contains(&"artist=B".to_string()));
+    }
+
+    #[test]
+    fn test_vorbiscomment_text() {
+        let mut tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        tag.add_one("lyrics".into(), "first line\r\nsecond\\line".into());
+        tag.add_one("title".into(), "Silence".into());
+
+        let text = tag.to_vorbiscomment_text();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "encoder=Lavc61.19.101 libopus");
+        assert!(lines[1].starts_with("METADATA_BLOCK_PICTURE="));
+        assert_eq!(lines[2], "lyrics=first line\\r\\nsecond\\\\line");
+
+        let mut parsed = Tag::from_vorbiscomment_text(&text).unwrap();
+        assert_eq!(parsed.get_vendor(), "");
+        parsed.set_vendor(tag.get_vendor().to_string());
+        assert_eq!(parsed, tag);
+        assert_eq!(parsed.comment_lines(), tag.comment_lines());
+
+        assert!(matches!(
+            Tag::from_vorbiscomment_text("TITLE=Silence\nnot a comment\n"),
+            Err(Error::MalformedComment(line)) if line == "not a comment"
+        ));
+        assert!(Tag::from_vorbiscomment_text("TITLE=\\x").is_err());
+    }
+
+    #[test]
+    fn test_packet_data_round_trip() {
+        let picture = large_picture_comment(PICTURE_BLOCK_TAG);
+        let header = comment_header(
+            "opusmeta",
+            &[b"artist=A", b"artist=B", &picture, b"title=Silence"],
+        );
+        let tag = Tag::read_from(stream_with_comment_header(header.clone())).unwrap();
+
+        let options = WriteOptions {
+            sort: true,
+            ..Default::default()
+        };
+        assert_eq!(tag.to_packet_data(&options).unwrap(), header);
+    }
+
+    #[test]
+    fn test_multiple_artists_round_trip() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let mut file = Cursor::new(data);
+
+        let mut tag = Tag::read_from(&mut file).unwrap();
+        assert!(tag.artists().is_empty());
+        tag.add_artist("Primary".into());
+        tag.add_artist("Featured".into());
+        tag.add_genre("Ambient".into());
+        file.set_position(0);
+        tag.write_to(&mut file).unwrap();
+
+        file.set_position(0);
+        let tag = Tag::read_from(&mut file).unwrap();
+        assert_eq!(tag.artists(), ["Primary", "Featured"]);
+        assert_eq!(tag.genres(), ["Ambient"]);
+        assert!(tag.performers().is_empty());
+    }
+
+    #[test]
+    fn test_read_from_unseekable() {
+        /// A reader which can't seek, and fails when reading past `limit` bytes.
+        struct Unseekable {
+            data: Vec<u8>,
+            position: usize,
+            limit: usize,
+        }
+
+        impl Read for Unseekable {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let end = (self.position + buf.len()).min(self.data.len());
+                if end > self.limit {
+                    return Err(std::io::Error::other("read past the comment header"));
+                }
+                let len = end - self.position;
+                buf[..len].copy_from_slice(&self.data[self.position..end]);
+                self.position = end;
+                Ok(len)
+            }
+        }
+
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        // the first two pages hold the identification and comment headers
+        let reader = Unseekable {
+            data,
+            position: 0,
+            limit: 348,
+        };
+        let tag = Tag::read_from_unseekable(reader).expect("Failed to read tags");
+        assert_eq!(tag.get_vendor(), "Lavf61.7.100");
+        assert!(tag.has_pictures());
+    }
+
+    #[test]
+    fn test_read_from_serial() {
+        let mut output = Cursor::new(vec![]);
+        let mut writer = PacketWriter::new(&mut output);
+        for (serial, title) in [(5, b"TITLE=First".as_slice()), (9, b"TITLE=Second")] {
+            let packets = [
+                OpusHead::default().to_bytes(),
+                comment_header("opusmeta", &[title]),
+            ];
+            for packet in packets {
+                writer
+                    .write_packet(packet, serial, PacketWriteEndInfo::EndPage, 0)
+                    .unwrap();
+            }
+            writer
+                .write_packet(
+                    vec![0xfc, 0xff, 0xfe],
+                    serial,
+                    PacketWriteEndInfo::EndStream,
+                    960,
+                )
+                .unwrap();
+        }
+        let data = output.into_inner();
+
+        let first = Tag::read_from(Cursor::new(&data)).unwrap();
+        assert_eq!(
+            first.get_one(&"title".into()).map(String::as_str),
+            Some("First")
+        );
+        let first = Tag::read_from_serial(Cursor::new(&data), 5).unwrap();
+        assert_eq!(
+            first.get_one(&"title".into()).map(String::as_str),
+            Some("First")
+        );
+        let second = Tag::read_from_serial(Cursor::new(&data), 9).unwrap();
+        assert_eq!(
+            second.get_one(&"title".into()).map(String::as_str),
+            Some("Second")
+        );
+        assert!(matches!(
+            Tag::read_from_serial(Cursor::new(&data), 3),
+            Err(Error::SerialNotFound(3))
+        ));
+    }
+
+    #[test]
+    fn test_oversized_vendor_length() {
+        let mut header = b"OpusTags".to_vec();
+        header.extend_from_slice(&u32::MAX.to_le_bytes());
+        header.extend_from_slice(b"opusmeta");
+        header.extend_from_slice(&0u32.to_le_bytes());
+
+        assert!(matches!(
+            parse_comment_header(&header),
+            Err(Error::TruncatedVendor)
+        ));
+        assert!(matches!(
+            Tag::read_from(stream_with_comment_header(header.clone())),
+            Err(Error::TruncatedVendor)
+        ));
+        assert!(matches!(
+            Tag::read_vendor(stream_with_comment_header(header)),
+            Err(Error::TruncatedVendor)
+        ));
+    }
+
+    #[test]
+    fn test_read_adversarial_input() {
+        let with_header =
+            |header: Vec<u8>| Tag::read_from_slice(stream_with_comment_header(header).get_ref());
+
+        assert!(matches!(
+            Tag::read_from_slice(b""),
+            Err(Error::MissingPacket)
+        ));
+        // a page cut off in the middle of its header
+        assert!(matches!(
+            Tag::read_from_slice(b"OggS"),
+            Err(Error::MissingPacket)
+        ));
+        assert!(matches!(
+            Tag::read_from_slice(b"OggS\0\x02\0\0\0\0"),
+            Err(Error::MissingPacket)
+        ));
+        assert!(Tag::read_from_slice(&[0x42; 1024]).is_err());
+
+        // a comment count far beyond the comments in the header
+        let mut header = comment_header("opusmeta", &[b"TITLE=Silence"]);
+        header[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            with_header(header),
+            Err(Error::TruncatedComment { index: 1 })
+        ));
+
+        // a comment length far beyond the end of the header
+        let mut header = comment_header("opusmeta", &[b"TITLE=Silence"]);
+        header[24..28].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            with_header(header),
+            Err(Error::TruncatedComment { index: 0 })
+        ));
+
+        // an ID3v2 tag claiming to be larger than the whole input
+        let mut data = b"ID3\x04\0\0\x7f\x7f\x7f\x7f".to_vec();
+        data.extend_from_slice(stream_with_comment_header(comment_header("", &[])).get_ref());
+        assert!(matches!(
+            Tag::read_from_slice(&data),
+            Err(Error::MissingPacket)
+        ));
+
+        // a picture whose data length is far beyond the end of the picture
+        let picture = Picture {
+            mime_type: "image/png".into(),
+            ..Picture::new()
+        };
+        let mut bytes = picture.to_bytes().unwrap();
+        let len = bytes.len();
+        bytes[len - 4..].copy_from_slice(&u32::MAX.to_be_bytes());
+        let entry = format!("METADATA_BLOCK_PICTURE={}", BASE64_STANDARD.encode(bytes));
+        let tag = with_header(comment_header("", &[entry.as_bytes()])).unwrap();
+        assert!(matches!(
+            tag.pictures_iter().next(),
+            Some(Err(Error::DataError(_)))
+        ));
+
+        // the regression corpus of the fuzz target
+        for entry in std::fs::read_dir("fuzz/corpus/read_from_slice").unwrap() {
+            let data = std::fs::read(entry.unwrap().path()).unwrap();
+            if let Ok(tag) = Tag::read_from_slice(&data) {
+                let _ = tag.pictures();
+            }
+            let _ = Tag::from_comment_header(&data);
+        }
+    }
+
+    #[test]
+    fn test_truncated_header_errors() {
+        let header = comment_header("opusmeta", &[b"ARTIST=A", b"TITLE=Silence"]);
+        let read_truncated =
+            |len: usize| Tag::read_from(stream_with_comment_header(header[..len].to_vec()));
+
+        // in the middle of the vendor length
+        assert!(matches!(read_truncated(10), Err(Error::TruncatedVendor)));
+        // in the middle of the vendor string
+        assert!(matches!(read_truncated(16), Err(Error::TruncatedVendor)));
+        // in the middle of the comment count
+        assert!(matches!(
+            read_truncated(22),
+            Err(Error::TruncatedCommentCount)
+        ));
+        // in the middle of the first comment
+        assert!(matches!(
+            read_truncated(30),
+            Err(Error::TruncatedComment { index: 0 })
+        ));
+        // in the middle of the length of the second comment
+        assert!(matches!(
+            read_truncated(38),
+            Err(Error::TruncatedComment { index: 1 })
+        ));
+        // in the middle of the second comment
+        assert!(matches!(
+            read_truncated(header.len() - 1),
+            Err(Error::TruncatedComment { index: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_utf8_comment_index() {
+        let header = comment_header(
+            "opusmeta",
+            &[b"ARTIST=A", b"TITLE=Silence", b"ALBUM=Qu\xffet", b"\xff=A"],
+        );
+        let err = Tag::read_from(stream_with_comment_header(header)).unwrap_err();
+        assert_eq!(err.to_string(), "Comment #2 (ALBUM) is not valid UTF-8");
+        let Error::CommentUTFError { index, key, .. } = err else {
+            panic!("expected a UTF-8 error, got {err:?}");
+        };
+        assert_eq!(index, 2);
+        assert_eq!(key.as_deref(), Some("ALBUM"));
+
+        let header = comment_header("opusmeta", &[b"ARTIST=A", b"\xff=A"]);
+        let result = Tag::read_from(stream_with_comment_header(header));
+        assert!(matches!(
+            result,
+            Err(Error::CommentUTFError {
+                index: 1,
+                key: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_encoder_is_distinct_from_vendor() {
+        let mut tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        assert_eq!(tag.get_vendor(), "Lavf61.7.100");
+        assert_eq!(tag.encoder(), Some("Lavc61.19.101 libopus"));
+
+        tag.set_encoder("opusenc from opus-tools 0.2");
+        assert_eq!(tag.encoder(), Some("opusenc from opus-tools 0.2"));
+        assert_eq!(tag.get(&"encoder".into()).unwrap().len(), 1);
+        assert_eq!(tag.get_vendor(), "Lavf61.7.100");
+    }
+
+    #[test]
+    fn test_write_renumbers_pages_after_header_grows() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let original_granules: Vec<u64> = page_headers(&data).iter().map(|p| p.2).collect();
+        let mut file = Cursor::new(data);
+
+        let mut tag = Tag::read_from(&mut file).unwrap();
+        let mut picture = Picture::new();
+        picture.picture_type = PictureType::CoverBack;
+        picture.mime_type = "image/png".into();
+        picture.data = vec![0x42; 200_000];
+        tag.add_picture(&picture).unwrap();
+        file.set_position(0);
+        tag.write_to(&mut file).unwrap();
+
+        let data = file.into_inner();
+        let pages = page_headers(&data);
+        // the comment header now spans several pages
+        assert!(pages.len() > original_granules.len() + 3);
+        assert_contiguous_pages(&data);
+        stream::verify_pages(&data).unwrap();
+
+        // the audio pages keep their granule positions
+        let granules: Vec<u64> = pages.iter().map(|p| p.2).collect();
+        assert!(granules.ends_with(&original_granules[2..]));
+
+        let tag = Tag::read_from_slice(&data).unwrap();
+        assert_eq!(tag.pictures().len(), 2);
+    }
+
+    #[test]
+    fn test_diff_against_file() {
+        let open = || File::open("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let mut tag = Tag::read_from(open()).unwrap();
+        assert!(tag.diff_against_file(open()).unwrap().is_empty());
+
+        tag.add_one("title".into(), "Silence".into());
+        tag.set_encoder("opusmeta");
+        tag.remove_picture_type(PictureType::CoverFront).unwrap();
+        let mut picture = Picture::new();
+        picture.picture_type = PictureType::CoverBack;
+        picture.mime_type = "image/png".into();
+        picture.data = vec![0x42; 16];
+        tag.add_picture(&picture).unwrap();
+
+        let diff = tag.diff_against_file(open()).unwrap();
+        assert_eq!(diff.vendor, None);
+        assert_eq!(diff.added, [("title".into(), vec!["Silence".into()])]);
+        assert!(diff.removed.is_empty());
+        assert_eq!(
+            diff.changed,
+            [KeyChange {
+                key: "encoder".into(),
+                old: vec!["Lavc61.19.101 libopus".into()],
+                new: vec!["opusmeta".into()],
+            }]
+        );
+        assert_eq!(diff.added_pictures().len(), 1);
+        assert_eq!(
+            diff.removed_pictures()[0].picture_type,
+            PictureType::CoverFront
+        );
+
+        let printed = diff.to_string();
+        assert!(printed.contains("+ title=Silence\n"));
+        assert!(printed.contains("~ encoder: [\"Lavc61.19.101 libopus\"] -> [\"opusmeta\"]\n"));
+        assert!(printed.contains("+ picture: CoverBack (image/png, 16 bytes)\n"));
+    }
+
+    #[test]
+    fn test_write_to_path_atomic() {
+        let dir = std::env::temp_dir().join(format!("opusmeta-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("silence.opus");
+        std::fs::copy("testfiles/silence_cover.opus", &path).unwrap();
+
+        let mut tag = Tag::read_from_path(&path).unwrap();
+        tag.add_one("title".into(), "Atomic".into());
+        tag.write_to_path_atomic(&path).unwrap();
+
+        assert_eq!(Tag::read_from_path(&path).unwrap(), tag);
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["silence.opus"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A file which records whether it was resized, and which can be made to fail after a number
+    /// of bytes were written.
+    struct RecordingFile {
+        inner: Cursor<Vec<u8>>,
+        write_limit: Option<usize>,
+        resized: bool,
+    }
+
+    impl Read for RecordingFile {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for RecordingFile {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    impl Write for RecordingFile {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            match self.write_limit {
+                Some(0) => Err(std::io::ErrorKind::WriteZero.into()),
+                Some(ref mut limit) => {
+                    let len = buf.len().min(*limit);
+                    *limit -= len;
+                    self.inner.write(&buf[..len])
+                }
+                None => self.inner.write(buf),
+            }
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl StorageFile for RecordingFile {
+        fn set_len(&mut self, new_size: u64) -> crate::Result<()> {
+            self.resized = true;
+            self.inner.set_len(new_size)
+        }
+    }
+
+    #[test]
+    fn test_write_shorter_leaves_no_trailing_bytes() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let mut tag = Tag::read_from(Cursor::new(&data)).unwrap();
+        tag.remove_entries(&"encoder".into()).unwrap();
+
+        let mut file = RecordingFile {
+            inner: Cursor::new(data.clone()),
+            write_limit: None,
+            resized: false,
+        };
+        tag.write_to(&mut file).unwrap();
+        let written = file.inner.into_inner();
+        assert!(written.len() < data.len());
+        stream::verify_pages(&written).unwrap();
+        assert_eq!(Tag::read_from(Cursor::new(&written)).unwrap(), tag);
+
+        // a failed write doesn't resize the target
+        let mut file = RecordingFile {
+            inner: Cursor::new(data.clone()),
+            write_limit: Some(100),
+            resized: false,
+        };
+        assert!(matches!(tag.write_to(&mut file), Err(Error::DataError(_))));
+        assert!(!file.resized);
+        assert_eq!(file.inner.get_ref().len(), data.len());
+    }
+
+    #[test]
+    fn test_write_in_place_without_pictures() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let header_len = stream::read_header_pages(Cursor::new(&data)).unwrap().len();
+        let mut tag = Tag::read_from(Cursor::new(&data)).unwrap();
+        tag.remove_entries(&PICTURE_BLOCK_TAG.into()).unwrap();
+        tag.add_one("title".into(), "Silence".into());
+
+        let mut file = RecordingFile {
+            inner: Cursor::new(data.clone()),
+            write_limit: Some(header_len),
+            resized: false,
+        };
+        // the picture freed up more than the default padding, so keep the freed space as padding
+        let freed = tag
+            .header_size_delta(Cursor::new(&data))
+            .unwrap()
+            .unsigned_abs();
+        let options = WriteOptions {
+            padding: usize::try_from(freed).unwrap(),
+            ..Default::default()
+        };
+        tag.write_to_opts(&mut file, &options).unwrap();
+        // only the header pages were written, and the audio pages are untouched
+        assert!(!file.resized);
+        let written = file.inner.into_inner();
+        assert_eq!(written.len(), data.len());
+        assert_eq!(written[header_len..], data[header_len..]);
+        stream::verify_pages(&written).unwrap();
+        let read = Tag::read_from(Cursor::new(&written)).unwrap();
+        assert_eq!(read, tag);
+        assert_eq!(read.trailing_data().len(), options.padding);
+        assert_eq!(
+            OpusHead::read_from(Cursor::new(&written)).unwrap(),
+            OpusHead::read_from(Cursor::new(&data)).unwrap()
+        );
+
+        // binary data after the comments can't be padded, so the stream is rewritten instead
+        tag.trailing_data = vec![0x01];
+        let mut file = Cursor::new(data.clone());
+        tag.write_to(&mut file).unwrap();
+        let written = file.into_inner();
+        assert!(written.len() < data.len());
+        assert_eq!(Tag::read_from(Cursor::new(&written)).unwrap(), tag);
+    }
+
+    #[test]
+    fn test_write_after_removing_pictures_shrinks_file() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let mut tag = Tag::read_from(Cursor::new(&data)).unwrap();
+        tag.strip_keys(&[PICTURE_BLOCK_TAG]);
+
+        // the freed space isn't kept as padding, which later writes would carry forward
+        let mut file = Cursor::new(data.clone());
+        tag.write_to(&mut file).unwrap();
+        let written = file.into_inner();
+        assert!(written.len() < data.len());
+        let read = Tag::read_from(Cursor::new(&written)).unwrap();
+        assert!(read.trailing_data().is_empty());
+
+        // writing the tag again doesn't grow the file
+        let mut file = Cursor::new(written.clone());
+        read.write_to(&mut file).unwrap();
+        assert_eq!(file.into_inner(), written);
+    }
+
+    #[test]
+    fn test_edit_file() {
+        let dir = std::env::temp_dir().join(format!("opusmeta-edit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("silence.opus");
+        std::fs::copy("testfiles/silence_cover.opus", &path).unwrap();
+
+        Tag::edit_file(&path, |tag| tag.add_one("title".into(), "Edited".into())).unwrap();
+
+        let tag = Tag::read_from_path(&path).unwrap();
+        assert_eq!(tag.get_vendor(), "Lavf61.7.100");
+        assert_eq!(tag.get_one(&"title".into()).unwrap(), "Edited");
+        assert_eq!(tag.encoder(), Some("Lavc61.19.101 libopus"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_dir() {
+        let dir = std::env::temp_dir().join(format!("opusmeta-read-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy("testfiles/silence_cover.opus", dir.join("a.opus")).unwrap();
+        std::fs::write(dir.join("b.opus"), b"not an opus file").unwrap();
+        std::fs::write(dir.join("c.txt"), b"ignored").unwrap();
+
+        let results: Vec<_> = read_dir(&dir).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, dir.join("a.opus"));
+        assert_eq!(results[0].1.as_ref().unwrap().get_vendor(), "Lavf61.7.100");
+        assert_eq!(results[1].0, dir.join("b.opus"));
+        assert!(results[1].1.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let results: Vec<_> = read_dir(&dir).collect();
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0].1, Err(Error::DataError(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_write_many() {
+        let dir = std::env::temp_dir().join(format!("opusmeta-write-many-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let jobs: Vec<_> = (0..8)
+            .map(|index| {
+                let path = dir.join(format!("{index}.opus"));
+                std::fs::copy("testfiles/silence_cover.opus", &path).unwrap();
+                let mut tag = Tag::read_from_path(&path).unwrap();
+                tag.add_one("tracknumber".into(), index.to_string());
+                (path, tag)
+            })
+            .collect();
+
+        let results = write_many(jobs.clone());
+        assert_eq!(results.len(), jobs.len());
+        for ((path, result), (expected_path, tag)) in results.into_iter().zip(jobs) {
+            assert_eq!(path, expected_path);
+            result.unwrap();
+            assert_eq!(Tag::read_from_path(&path).unwrap(), tag);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_copy_tags() {
+        let dir = std::env::temp_dir().join(format!("opusmeta-copy-tags-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let from = dir.join("from.opus");
+        let to = dir.join("to.opus");
+        std::fs::copy("testfiles/silence_cover.opus", &from).unwrap();
+        std::fs::copy("testfiles/silence_cover.opus", &to).unwrap();
+
+        let mut source = Tag::read_from_path(&from).unwrap();
+        source.set_vendor("opusmeta".into());
+        source.add_one("title".into(), "Silence".into());
+        source.write_to_path(&from).unwrap();
+        Tag::new("libopus".into(), vec![("ARTIST".into(), "A".into())])
+            .write_to_path(&to)
+            .unwrap();
+
+        // the two paths don't have to share a type
+        copy_tags(&from, to.to_str().unwrap()).unwrap();
+        let copied = Tag::read_from_path(&to).unwrap();
+        assert_eq!(copied, source);
+        assert_eq!(copied.get_vendor(), "opusmeta");
+        assert!(copied.has_pictures());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_matches_file() {
+        let data = std::fs::read("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let mut tag = Tag::read_from(Cursor::new(&data)).unwrap();
+        assert!(tag.matches_file(Cursor::new(&data)).unwrap());
+
+        tag.add_one("title".into(), "Silence".into());
+        assert!(!tag.matches_file(Cursor::new(&data)).unwrap());
+
+        let mut file = Cursor::new(data);
+        tag.write_to(&mut file).unwrap();
+        file.set_position(0);
+        assert!(tag.matches_file(&mut file).unwrap());
+
+        // equal tags can still encode to different comment headers
+        let header = comment_header("opusmeta", &[b"TITLE=Silence", b"ARTIST=A"]);
+        let data = stream_with_comment_header(header).into_inner();
+        let tag = Tag::read_from(Cursor::new(&data)).unwrap();
+        assert!(tag.matches_file(Cursor::new(&data)).unwrap());
+        for comments in [
+            [b"title=Silence".as_slice(), b"ARTIST=A"],
+            [b"ARTIST=A", b"TITLE=Silence"],
+        ] {
+            let other = Tag::from_comment_header(&comment_header("opusmeta", &comments)).unwrap();
+            assert_eq!(other, tag);
+            assert!(!other.matches_file(Cursor::new(&data)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_clone() {
+        let tag =
+            Tag::read_from_path("testfiles/silence_cover.opus").expect("Failed to open testfile");
+        let mut clone = tag.clone();
+        assert_eq!(clone.vendor, tag.vendor);
+        assert_eq!(clone.comments, tag.comments);
+
+        clone.add_artist("Someone".into());
+        assert!(tag.artists().is_empty());
+    }
+
+    #[test]
+    fn test_equality() {
+        let build = |artists: [&str; 2]| {
+            let mut tag = Tag::new("opusmeta".into(), vec![]);
+            tag.add_one("title".into(), "Silence".into());
+            for artist in artists {
+                tag.add_artist(artist.into());
+            }
+            tag
+        };
+
+        assert_eq!(build(["A", "B"]), build(["A", "B"]));
+        assert_ne!(build(["A", "B"]), build(["A", "C"]));
+        // the order of the values within a key is significant
+        assert_ne!(build(["A", "B"]), build(["B", "A"]));
+
+        let mut other_vendor = build(["A", "B"]);
+        other_vendor.set_vendor("other".into());
+        assert_ne!(build(["A", "B"]), other_vendor);
+    }
+
+    #[test]
+    fn test_parse_comment_header() {
+        let header = comment_header(
+            "opusmeta",
+            &[b"ARTIST=A", b"TITLE=Silence", b"artist=B", b"DESCRIPTION="],
+        );
+        let parsed = parse_comment_header(&header).unwrap();
+        let tag = Tag::read_from(stream_with_comment_header(header.clone())).unwrap();
+
+        assert_eq!(parsed.vendor(), tag.get_vendor());
+        assert_eq!(parsed.comment_count(), 4);
+
+        let comments: Vec<(&str, &str)> = parsed.comments().collect();
+        assert_eq!(
+            comments,
+            [
+                ("ARTIST", "A"),
+                ("TITLE", "Silence"),
+                ("artist", "B"),
+                ("DESCRIPTION", "")
+            ]
+        );
+        let rebuilt: Tag = comments
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(rebuilt.comments, tag.comments);
+
+        for ((key, value), (key_range, value_range)) in
+            parsed.comments().zip(parsed.comment_ranges())
+        {
+            assert_eq!(key.as_bytes(), &header[key_range]);
+            assert_eq!(value.as_bytes(), &header[value_range]);
+        }
+    }
 }
@@ -0,0 +1,78 @@
+/// Options which control how a [`Tag`](crate::Tag) is written.
+///
+/// The default options write the comments exactly as [`Tag::write_to`](crate::Tag::write_to)
+/// does.
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Sort the comments by key, and by value within a key, before encoding them. This makes the
+    /// output independent of the order in which comments were added. The picture block is sorted
+    /// along with the rest of the keys, and the pictures within it are sorted by their encoded
+    /// data.
+    pub sort: bool,
+    /// The minimum number of padding bytes after the comments in the comment header. Reserving
+    /// padding allows later edits to grow the comments without moving the rest of the stream.
+    ///
+    /// Data following the comments in the original comment header is always kept. If it is
+    /// padding, zero bytes are added until it is at least this long. If it is binary data (the
+    /// lowest bit of its first byte is set), it is kept as-is and no padding is added.
+    ///
+    /// This also bounds the padding left behind when a shorter comment header is patched in
+    /// place (see [`Tag::write_to`](crate::Tag::write_to)).
+    pub padding: usize,
+    /// Where the picture entries are placed among the other comments. The pictures keep their
+    /// relative order either way.
+    pub picture_placement: PicturePlacement,
+    /// Error with [`Error::ControlCharacter`](crate::Error::ControlCharacter) if a comment value
+    /// contains a C0 control character (`U+0000` to `U+001F`, including NUL), which can confuse
+    /// players. Tabs, line feeds and carriage returns are allowed, since they are common in
+    /// multi-line values such as lyrics. Pictures are not checked.
+    pub reject_control_chars: bool,
+}
+
+/// Where the picture entries are placed among the other comments when writing. See
+/// [`WriteOptions::picture_placement`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum PicturePlacement {
+    /// Wherever the picture block falls in the order of the keys. This order is unspecified,
+    /// unless [`WriteOptions::sort`] is set.
+    #[default]
+    InOrder,
+    /// Before all other comments.
+    First,
+    /// After all other comments.
+    Last,
+}
+
+/// Options which control how a [`Tag`](crate::Tag) is read.
+///
+/// The default options are strict, and read the comments exactly as
+/// [`Tag::read_from`](crate::Tag::read_from) does.
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ReadOptions {
+    /// Treat a comment which is not in TAG=VALUE format (e.g. a bare `TAG` written as a flag by
+    /// some taggers) as a tag with an empty value, instead of erroring with
+    /// [`Error::MalformedComment`](crate::Error::MalformedComment).
+    pub allow_bare_keys: bool,
+    /// The maximum number of pictures to read. Pictures past the limit are dropped, or cause an
+    /// error if [`reject_excess_pictures`](Self::reject_excess_pictures) is set. This caps the
+    /// resources spent on untrusted input. Pictures are not limited by default.
+    pub max_pictures: Option<usize>,
+    /// Error with [`Error::TooManyPictures`](crate::Error::TooManyPictures) instead of dropping
+    /// the pictures past [`max_pictures`](Self::max_pictures).
+    pub reject_excess_pictures: bool,
+    /// Recover from corrupt files whose second packet is not the comment header (e.g. because
+    /// an audio packet took its place), by scanning the following packets of the stream for one
+    /// starting with `OpusTags`. Only a few packets are scanned, after which
+    /// [`Error::MissingPacket`](crate::Error::MissingPacket) is returned.
+    ///
+    /// Without this option, the second packet is always parsed as the comment header.
+    pub scan_for_tags: bool,
+    /// Remove spaces around the `=` separator, e.g. reading `ALBUM = X` as the key `ALBUM` with
+    /// the value `X`. Spaces at the start of the key are removed as well. Some taggers write such
+    /// comments, although keys can't contain spaces per the spec.
+    pub trim_key_whitespace: bool,
+    /// Remove a trailing carriage return from values, which is left over by taggers that write
+    /// values with CRLF line endings.
+    pub trim_trailing_cr: bool,
+}
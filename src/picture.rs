@@ -3,14 +3,16 @@
 //! This crate uses the [METADATA_BLOCK_PICTURE](https://wiki.xiph.org/VorbisComment#Cover_art)
 //! proposal to encode pictures into Opus Comments.
 
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use std::fs::OpenOptions;
-use std::io::{Cursor, Read, Seek};
+use std::io::{Cursor, Read};
 use std::path::Path;
 
-use base64::prelude::{BASE64_STANDARD, Engine as _};
+use base64::prelude::{
+    BASE64_STANDARD, BASE64_STANDARD_NO_PAD, BASE64_URL_SAFE, BASE64_URL_SAFE_NO_PAD, Engine as _,
+};
 
-use crate::Result;
+use crate::{Result, utils};
 
 /// Type of picture, according to the APIC picture standard.
 ///
@@ -55,6 +57,116 @@ impl PictureType {
             Ok(unsafe { std::mem::transmute::<u32, Self>(num) })
         }
     }
+
+    /// Returns the variant name in snake case (e.g. `cover_front`), for use in file names.
+    pub(crate) fn file_stem(self) -> String {
+        let mut stem = String::new();
+        for c in format!("{self:?}").chars() {
+            if c.is_ascii_uppercase() && !stem.is_empty() {
+                stem.push('_');
+            }
+            stem.push(c.to_ascii_lowercase());
+        }
+        stem
+    }
+
+    /// Returns the descriptive name of this picture type, as given in the FLAC spec (e.g.
+    /// `"Cover (front)"`).
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Other => "Other",
+            Self::FileIcon => "32x32 pixels 'file icon' (PNG only)",
+            Self::OtherIcon => "Other file icon",
+            Self::CoverFront => "Cover (front)",
+            Self::CoverBack => "Cover (back)",
+            Self::LeafletPage => "Leaflet page",
+            Self::Media => "Media (e.g. label side of CD)",
+            Self::LeadArtist => "Lead artist/lead performer/soloist",
+            Self::Artist => "Artist/performer",
+            Self::Conductor => "Conductor",
+            Self::BandOrchestra => "Band/Orchestra",
+            Self::Composter => "Composer",
+            Self::Lyricist => "Lyricist/text writer",
+            Self::RecordingLocation => "Recording Location",
+            Self::DuringRecording => "During recording",
+            Self::DuringPerformance => "During performance",
+            Self::MovieCapture => "Movie/video screen capture",
+            Self::BrightColouredFish => "A bright coloured fish",
+            Self::Illustration => "Illustration",
+            Self::BandLogo => "Band/artist logotype",
+            Self::PublisherLogo => "Publisher/Studio logotype",
+        }
+    }
+
+    /// Parses a picture type from either its variant name (e.g. `"CoverFront"`) or its
+    /// descriptive [`name`](Self::name) (e.g. `"Cover (front)"`), ignoring case. Returns None if
+    /// the name doesn't match any picture type.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        (0..=20)
+            .filter_map(|num| Self::from_u32(num).ok())
+            .find(|picture_type| {
+                name.eq_ignore_ascii_case(picture_type.name())
+                    || name.eq_ignore_ascii_case(&format!("{picture_type:?}"))
+            })
+    }
+
+    /// Returns whether this picture type is an icon ([`FileIcon`](Self::FileIcon) or
+    /// [`OtherIcon`](Self::OtherIcon)).
+    #[must_use]
+    pub const fn is_icon(&self) -> bool {
+        matches!(self, Self::FileIcon | Self::OtherIcon)
+    }
+
+    /// Returns whether this picture type is a cover ([`CoverFront`](Self::CoverFront) or
+    /// [`CoverBack`](Self::CoverBack)).
+    #[must_use]
+    pub const fn is_cover(&self) -> bool {
+        matches!(self, Self::CoverFront | Self::CoverBack)
+    }
+
+    /// Returns the broad [`PictureCategory`] this picture type belongs to.
+    #[must_use]
+    pub const fn category(&self) -> PictureCategory {
+        match self {
+            Self::FileIcon | Self::OtherIcon => PictureCategory::Icon,
+            Self::CoverFront | Self::CoverBack | Self::LeafletPage | Self::Media => {
+                PictureCategory::Artwork
+            }
+            Self::LeadArtist
+            | Self::Artist
+            | Self::Conductor
+            | Self::BandOrchestra
+            | Self::Composter
+            | Self::Lyricist => PictureCategory::Person,
+            Self::RecordingLocation
+            | Self::DuringRecording
+            | Self::DuringPerformance
+            | Self::MovieCapture => PictureCategory::Recording,
+            Self::BandLogo | Self::PublisherLogo => PictureCategory::Logo,
+            Self::Other | Self::BrightColouredFish | Self::Illustration => PictureCategory::Other,
+        }
+    }
+}
+
+/// Broad grouping of [`PictureType`]s, useful for UI grouping.
+///
+/// See [`PictureType::category`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PictureCategory {
+    /// File icons.
+    Icon,
+    /// Covers, leaflet pages and pictures of the media itself.
+    Artwork,
+    /// Pictures of the people credited on the recording.
+    Person,
+    /// Pictures taken at or of the recording itself.
+    Recording,
+    /// Band or publisher logos.
+    Logo,
+    /// Anything else.
+    Other,
 }
 
 /// Errors that could be raised while encoding or decoding a [`Picture`].
@@ -72,6 +184,14 @@ pub enum PictureError {
     Base64DecodeError(base64::DecodeError),
     /// Failed to sniff a mime type from a file.
     NoMimeType,
+    /// The MIME type was empty.
+    EmptyMimeType,
+    /// The picture data was empty.
+    EmptyData,
+    /// The MIME type was not in `type/subtype` form.
+    InvalidMimeType,
+    /// The MIME type didn't match the MIME type sniffed from the picture data.
+    MimeMismatch,
 }
 
 impl Display for PictureError {
@@ -83,6 +203,10 @@ impl Display for PictureError {
             Self::DataTooLong => "Picture data is too long (more than u32::MAX bytes long!)",
             Self::Base64DecodeError(_) => "Failed to decode base64 data",
             Self::NoMimeType => "Failed to sniff mime type from file",
+            Self::EmptyMimeType => "MIME type is empty",
+            Self::EmptyData => "Picture data is empty",
+            Self::InvalidMimeType => "MIME type is not in type/subtype form",
+            Self::MimeMismatch => "MIME type does not match the picture data",
         })
     }
 }
@@ -97,29 +221,213 @@ impl From<base64::DecodeError> for PictureError {
 
 /// Stores picture data.
 ///
-/// The `width`. `height`, `depth`, and `num_colors` fields should be left as
-/// 0 if possible.
+/// The `width`, `height`, `depth`, and `num_colors` fields are informational and may be left as
+/// 0 if unknown.
+///
+/// The `Debug` output only shows the length of the picture data, to keep logs readable.
 #[allow(dead_code)]
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone)]
 pub struct Picture {
     pub picture_type: PictureType,
     pub mime_type: String,
     pub description: String,
+    /// The width of the picture in pixels.
+    pub width: u32,
+    /// The height of the picture in pixels.
+    pub height: u32,
+    /// The color depth of the picture in bits per pixel.
+    pub depth: u32,
+    /// The number of colors used for indexed-color pictures (e.g. GIF), or 0 otherwise.
+    pub num_colors: u32,
     pub data: Vec<u8>,
 }
 
+impl Debug for Picture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Picture")
+            .field("picture_type", &self.picture_type)
+            .field("mime_type", &self.mime_type)
+            .field("description", &self.description)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("depth", &self.depth)
+            .field("num_colors", &self.num_colors)
+            .field("data", &format_args!("[{} bytes]", self.data.len()))
+            .finish()
+    }
+}
+
 impl Picture {
     #[must_use]
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Creates a new picture, validating the MIME type.
+    ///
+    /// The MIME type must be in `type/subtype` form (e.g. `image/png`), or `-->` if the data is a
+    /// URL to the picture, as allowed by the FLAC spec. If the type of the picture data can be
+    /// sniffed, the MIME type must also match it.
+    /// # Errors
+    /// This function will return an error if the MIME type is empty or malformed, or if it
+    /// doesn't match the picture data.
+    pub fn new_checked(
+        picture_type: PictureType,
+        mime_type: String,
+        description: String,
+        data: Vec<u8>,
+    ) -> std::result::Result<Self, PictureError> {
+        if mime_type.is_empty() {
+            return Err(PictureError::EmptyMimeType);
+        }
+        if mime_type != "-->" {
+            // a token as defined by RFC 2045, excluding the "/" separator
+            let valid_part = |part: &str| {
+                !part.is_empty()
+                    && part
+                        .bytes()
+                        .all(|byte| byte.is_ascii_graphic() && !MIME_SPECIALS.contains(&byte))
+            };
+            let Some((kind, subtype)) = mime_type.split_once('/') else {
+                return Err(PictureError::InvalidMimeType);
+            };
+            if !valid_part(kind) || !valid_part(subtype) {
+                return Err(PictureError::InvalidMimeType);
+            }
+            if !mime_matches(&mime_type, &data) {
+                return Err(PictureError::MimeMismatch);
+            }
+        }
+
+        Ok(Self {
+            picture_type,
+            mime_type,
+            description,
+            data,
+            ..Self::new()
+        })
+    }
+
+    /// Returns a reader over the picture data, e.g. for passing the picture to an image decoder.
+    #[must_use]
+    pub fn data_reader(&self) -> Cursor<&[u8]> {
+        Cursor::new(&self.data)
+    }
+
+    /// Reads the width and height of the picture from the picture data. Only PNG, JPEG and GIF
+    /// pictures are supported. Returns None if the format is not supported, or if the data is
+    /// malformed.
+    #[must_use]
+    pub fn decode_dimensions(&self) -> Option<(u32, u32)> {
+        let data = self.data.as_slice();
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            // the IHDR chunk always comes first
+            if data.get(12..16)? != b"IHDR" {
+                return None;
+            }
+            Some((
+                read_be_u32(data.get(16..20)?),
+                read_be_u32(data.get(20..24)?),
+            ))
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            let width = u16::from_le_bytes([*data.get(6)?, *data.get(7)?]);
+            let height = u16::from_le_bytes([*data.get(8)?, *data.get(9)?]);
+            Some((width.into(), height.into()))
+        } else if data.starts_with(&[0xff, 0xd8]) {
+            jpeg_dimensions(data)
+        } else {
+            None
+        }
+    }
+
+    /// Decodes the picture data into an image using the [`image`] crate. The MIME type is used as
+    /// a hint for the image format if it is recognized, otherwise the format is guessed from the
+    /// data.
+    /// # Errors
+    /// This function will return an error if the format of the picture is not supported, or if
+    /// the picture data is malformed.
+    #[cfg(feature = "image")]
+    pub fn decode_image(&self) -> image::ImageResult<image::DynamicImage> {
+        image::ImageFormat::from_mime_type(&self.mime_type).map_or_else(
+            || image::load_from_memory(&self.data),
+            |format| image::load_from_memory_with_format(&self.data, format),
+        )
+    }
+
+    /// Encodes an image in the given format using the [`image`] crate, and builds a picture from
+    /// it. The MIME type and the dimensions of the picture are set to match the encoded image.
+    /// # Errors
+    /// This function will return an error if the image can't be encoded in the given format.
+    #[cfg(feature = "image")]
+    pub fn from_image(
+        image: &image::DynamicImage,
+        format: image::ImageFormat,
+        picture_type: PictureType,
+    ) -> Result<Self> {
+        let mut data = Cursor::new(Vec::new());
+        image.write_to(&mut data, format)?;
+
+        Ok(Self {
+            picture_type,
+            mime_type: format.to_mime_type().to_string(),
+            width: image.width(),
+            height: image.height(),
+            depth: image.color().bits_per_pixel().into(),
+            data: data.into_inner(),
+            ..Self::new()
+        })
+    }
+
+    /// Checks whether the MIME type matches the format sniffed from the picture data, e.g. returns
+    /// false for PNG data labeled as `image/jpeg`. Returns true if the format of the data can't
+    /// be detected.
+    #[must_use]
+    pub fn mime_matches_data(&self) -> bool {
+        mime_matches(&self.mime_type, &self.data)
+    }
+
+    /// Guesses a file extension for the picture (e.g. `jpg`), from the sniffed type of the data
+    /// or otherwise the MIME type. Returns `bin` if neither is recognized.
+    pub(crate) fn file_extension(&self) -> &str {
+        let mime_type = sniff_mime_type(&self.data).unwrap_or(&self.mime_type);
+        match mime_type {
+            "image/jpeg" | "image/jpg" => "jpg",
+            _ => mime_type
+                .strip_prefix("image/")
+                .filter(|subtype| {
+                    !subtype.is_empty() && subtype.bytes().all(|byte| byte.is_ascii_alphanumeric())
+                })
+                .unwrap_or("bin"),
+        }
+    }
+
     /// Attempts to decode a Picture object from a byte slice formatted in the FLAC picture format. See
     /// <https://xiph.org/flac/format.html#metadata_block_picture> for more info.
     /// # Errors
     /// This function can error if the slice is shorter than expected, or if the system platform's
     /// usize is not big enough (See [`Error::PlatformError`](crate::Error::PlatformError) for more information).
     pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::decode(data, false)
+    }
+
+    /// Like [`from_bytes`](Self::from_bytes), but invalid UTF-8 in the MIME type or the
+    /// description is replaced with `U+FFFD REPLACEMENT CHARACTER` instead of causing an error.
+    /// The picture data is always kept intact.
+    /// # Errors
+    /// This function can error for the same reasons as [`from_bytes`](Self::from_bytes), except
+    /// for invalid UTF-8.
+    pub fn from_bytes_lossy(data: &[u8]) -> Result<Self> {
+        Self::decode(data, true)
+    }
+
+    fn decode(data: &[u8], lossy: bool) -> Result<Self> {
+        let to_string = |buffer: Vec<u8>| -> Result<String> {
+            if lossy {
+                Ok(String::from_utf8_lossy(&buffer).into_owned())
+            } else {
+                Ok(String::from_utf8(buffer)?)
+            }
+        };
         let mut cursor = Cursor::new(data);
 
         // picture type
@@ -128,39 +436,41 @@ impl Picture {
         let picture_type = PictureType::from_u32(u32::from_be_bytes(buffer))?;
 
         // mime type
-        let mut buffer = [0; 4];
-        cursor.read_exact(&mut buffer)?;
-        let mime_length: usize = u32::from_be_bytes(buffer).try_into()?;
-        let mut buffer = vec![0; mime_length];
-        cursor.read_exact(&mut buffer)?;
-        let mime_type = String::from_utf8(buffer)?;
+        let mime_type = to_string(read_length_prefixed(&mut cursor)?)?;
 
         // description
-        let mut buffer = [0; 4];
-        cursor.read_exact(&mut buffer)?;
-        let desc_length: usize = u32::from_be_bytes(buffer).try_into()?;
-        let mut buffer = vec![0; desc_length];
-        cursor.read_exact(&mut buffer)?;
-        let description = String::from_utf8(buffer)?;
+        let description = to_string(read_length_prefixed(&mut cursor)?)?;
 
-        // skip width, height, depth, and num_colors (4 bytes each)
-        cursor.seek_relative(16)?;
+        // width, height, depth, and num_colors
+        let mut buffer = [0; 16];
+        cursor.read_exact(&mut buffer)?;
+        let [width, height, depth, num_colors] =
+            [0, 4, 8, 12].map(|i| read_be_u32(&buffer[i..i + 4]));
 
         // data
-        let mut buffer = [0; 4];
-        cursor.read_exact(&mut buffer)?;
-        let data_length: usize = u32::from_be_bytes(buffer).try_into()?;
-        let mut data = vec![0; data_length];
-        cursor.read_exact(&mut data)?;
+        let data = read_length_prefixed(&mut cursor)?;
 
         Ok(Self {
             picture_type,
             mime_type,
             description,
+            width,
+            height,
+            depth,
+            num_colors,
             data,
         })
     }
 
+    /// Truncates the description to at most `max_bytes` bytes, for players which fail to read
+    /// pictures with long descriptions. The description is cut at the last UTF-8 character
+    /// boundary within the limit, so it may end up slightly shorter.
+    #[must_use]
+    pub fn with_description_limit(mut self, max_bytes: usize) -> Self {
+        utils::truncate_at_char_boundary(&mut self.description, max_bytes);
+        self
+    }
+
     /// Encodes this Picture into the FLAC picture format. See
     /// <https://xiph.org/flac/format.html#metadata_block_picture> for more info.
     /// # Errors
@@ -186,10 +496,9 @@ impl Picture {
         output.extend_from_slice(&desc_length.to_be_bytes());
         output.extend_from_slice(self.description.as_bytes());
 
-        // write zeros for width, height, depth, and num_colors (4 bytes each)
-        // because honestly i dont care about these
-        let zero = [0; 16];
-        output.extend_from_slice(&zero);
+        for field in [self.width, self.height, self.depth, self.num_colors] {
+            output.extend_from_slice(&field.to_be_bytes());
+        }
 
         let data_len: u32 = self
             .data
@@ -215,32 +524,53 @@ impl Picture {
 
     /// Decodes a Picture from base64-encoded FLAC format, as specified by the vorbis picture
     /// proposal.
+    ///
+    /// The proposal mandates standard, padded base64, but some writers use unpadded or URL-safe
+    /// base64 instead, which is accepted as well.
     /// # Errors
     /// This function can error if the input string is not valid base64, or if
     /// [`Picture::from_bytes`] errors.
     pub fn from_base64(data: &str) -> Result<Self> {
-        let bytes = BASE64_STANDARD.decode(data).map_err(PictureError::from)?;
+        let bytes = decode_base64(data)?;
         let pic = Self::from_bytes(&bytes)?;
 
         Ok(pic)
     }
 
+    /// Like [`from_base64`](Self::from_base64), but decodes the picture with
+    /// [`from_bytes_lossy`](Self::from_bytes_lossy).
+    /// # Errors
+    /// This function can error if the input string is not valid base64, or if
+    /// [`Picture::from_bytes_lossy`] errors.
+    pub fn from_base64_lossy(data: &str) -> Result<Self> {
+        let bytes = decode_base64(data)?;
+        Self::from_bytes_lossy(&bytes)
+    }
+
     /// Reads a picture from the reader. If `mime_type` is None, then this function attempts to guess
     /// the mime type based on the input data.
+    ///
+    /// The mime type is guessed from the leading bytes of the input only (see
+    /// [`MIME_SNIFF_LIMIT`]), before the rest of the input is read. If a mime type is supplied, no
+    /// guessing is done at all.
     /// # Errors
     /// This function can error if reading from the input fails, or if guessing the mime type from
     /// the input data fails.
     pub fn read_from<R: Read>(mut f_in: R, mime_type: Option<String>) -> Result<Self> {
         let mut output = vec![];
-        f_in.read_to_end(&mut output)?;
 
+        if mime_type.is_none() {
+            (&mut f_in)
+                .take(MIME_SNIFF_LIMIT as u64)
+                .read_to_end(&mut output)?;
+        }
         let mime_type = match mime_type {
             Some(s) => s,
-            None => infer::get(&output)
+            None => sniff_mime_type(&output)
                 .ok_or(PictureError::NoMimeType)?
-                .mime_type()
                 .into(),
         };
+        f_in.read_to_end(&mut output)?;
 
         let mut pic = Self::new();
         pic.mime_type = mime_type;
@@ -257,3 +587,470 @@ impl Picture {
         Self::read_from(file, mime_type)
     }
 }
+
+/// The number of leading bytes [`Picture::read_from`] inspects when guessing the mime type of a
+/// picture.
+pub const MIME_SNIFF_LIMIT: usize = 8192;
+
+/// Reads a field prefixed with its length as a big endian u32. The length is checked against the
+/// remaining data before anything is allocated, so that a corrupt length can't cause a huge
+/// allocation.
+fn read_length_prefixed(cursor: &mut Cursor<&[u8]>) -> Result<Vec<u8>> {
+    let mut buffer = [0; 4];
+    cursor.read_exact(&mut buffer)?;
+    let length = u64::from(u32::from_be_bytes(buffer));
+    let remaining =
+        cursor.get_ref().len() as u64 - cursor.position().min(cursor.get_ref().len() as u64);
+    if length > remaining {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    let mut field = Vec::new();
+    cursor.take(length).read_to_end(&mut field)?;
+    Ok(field)
+}
+
+fn read_be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes.try_into().expect("slice has length 4"))
+}
+
+/// Reads the dimensions of a JPEG image from its first start-of-frame segment.
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let read_u16 = |position: usize| -> Option<u16> {
+        Some(u16::from_be_bytes([
+            *data.get(position)?,
+            *data.get(position + 1)?,
+        ]))
+    };
+
+    // skip the start-of-image marker
+    let mut position = 2;
+    loop {
+        if *data.get(position)? != 0xff {
+            return None;
+        }
+        // markers may be preceded by any number of fill bytes
+        while *data.get(position)? == 0xff {
+            position += 1;
+        }
+        let marker = data[position];
+        match marker {
+            // SOF0 to SOF15, excluding DHT, JPG and DAC
+            0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                // segment length and sample precision precede the dimensions
+                let height = read_u16(position + 4)?;
+                let width = read_u16(position + 6)?;
+                return Some((width.into(), height.into()));
+            }
+            // markers without a segment
+            0x01 | 0xd0..=0xd7 => position += 1,
+            // end of image or start of scan, without having found the dimensions
+            0xd9 | 0xda => return None,
+            _ => position += 1 + usize::from(read_u16(position + 1)?),
+        }
+    }
+}
+
+/// Characters which aren't allowed in the type or subtype of a MIME type.
+const MIME_SPECIALS: &[u8] = b"()<>@,;:\\\"/[]?=";
+
+/// Guesses the mime type of picture data using `infer`, falling back to explicit checks for
+/// modern formats which `infer` may not identify.
+fn sniff_mime_type(data: &[u8]) -> Option<&'static str> {
+    if let Some(kind) = infer::get(data) {
+        return Some(kind.mime_type());
+    }
+
+    if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if data.len() >= 12 && &data[4..12] == b"ftypavif" {
+        Some("image/avif")
+    } else {
+        None
+    }
+}
+
+/// Decodes standard base64, falling back to unpadded and URL-safe base64. If none of them
+/// succeed, the error of the standard decoding is returned.
+fn decode_base64(data: &str) -> std::result::Result<Vec<u8>, PictureError> {
+    BASE64_STANDARD.decode(data).or_else(|err| {
+        [
+            BASE64_STANDARD_NO_PAD,
+            BASE64_URL_SAFE,
+            BASE64_URL_SAFE_NO_PAD,
+        ]
+        .iter()
+        .find_map(|engine| engine.decode(data).ok())
+        .ok_or_else(|| err.into())
+    })
+}
+
+/// Compares a declared mime type against the sniffed type of the data, treating `image/jpg` as
+/// `image/jpeg`. Returns true if the data can't be sniffed.
+fn mime_matches(mime_type: &str, data: &[u8]) -> bool {
+    let Some(sniffed) = sniff_mime_type(data) else {
+        return true;
+    };
+    let declared = if mime_type.eq_ignore_ascii_case("image/jpg") {
+        "image/jpeg"
+    } else {
+        mime_type
+    };
+    declared.eq_ignore_ascii_case(sniffed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picture_type_categories() {
+        assert!(PictureType::FileIcon.is_icon());
+        assert!(!PictureType::CoverFront.is_icon());
+        assert!(PictureType::CoverBack.is_cover());
+        assert!(!PictureType::Media.is_cover());
+
+        assert_eq!(PictureType::OtherIcon.category(), PictureCategory::Icon);
+        assert_eq!(PictureType::CoverFront.category(), PictureCategory::Artwork);
+        assert_eq!(PictureType::Conductor.category(), PictureCategory::Person);
+        assert_eq!(
+            PictureType::DuringPerformance.category(),
+            PictureCategory::Recording
+        );
+        assert_eq!(PictureType::BandLogo.category(), PictureCategory::Logo);
+        assert_eq!(PictureType::Other.category(), PictureCategory::Other);
+    }
+
+    #[test]
+    fn picture_type_name_round_trip() {
+        for num in 0..=20 {
+            let picture_type = PictureType::from_u32(num).unwrap();
+            assert_eq!(
+                PictureType::from_name(picture_type.name()),
+                Some(picture_type)
+            );
+            assert_eq!(
+                PictureType::from_name(&format!("{picture_type:?}")),
+                Some(picture_type)
+            );
+        }
+
+        assert_eq!(
+            PictureType::from_name("cover (FRONT)"),
+            Some(PictureType::CoverFront)
+        );
+        assert_eq!(
+            PictureType::from_name("coverback"),
+            Some(PictureType::CoverBack)
+        );
+        assert_eq!(PictureType::from_name("Cover"), None);
+    }
+
+    #[test]
+    fn read_webp_mime_type() {
+        let data = b"RIFF\x24\0\0\0WEBPVP8 \x18\0\0\0";
+        let picture = Picture::read_from(&data[..], None).unwrap();
+        assert_eq!(picture.mime_type, "image/webp");
+    }
+
+    #[test]
+    fn read_avif_mime_type() {
+        let data = b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf";
+        let picture = Picture::read_from(&data[..], None).unwrap();
+        assert_eq!(picture.mime_type, "image/avif");
+    }
+
+    /// A reader which fails once more than `limit` bytes have been read from it.
+    struct FailAfter<'a> {
+        data: &'a [u8],
+        limit: usize,
+    }
+
+    impl Read for FailAfter<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.limit == 0 {
+                return Err(std::io::Error::other("read past the limit"));
+            }
+            let len = buf.len().min(self.limit).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            self.limit -= len;
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn decode_invalid_utf8_description() {
+        let picture = Picture {
+            picture_type: PictureType::CoverFront,
+            mime_type: "image/png".into(),
+            description: "cover".into(),
+            data: vec![1, 2, 3, 4],
+            ..Picture::new()
+        };
+        let mut bytes = picture.to_bytes().unwrap();
+        // picture type, mime length, "image/png", description length
+        let description_start = 4 + 4 + 9 + 4;
+        bytes[description_start] = 0xff;
+
+        assert!(matches!(
+            Picture::from_bytes(&bytes),
+            Err(crate::Error::UTFError(_))
+        ));
+        let lossy = Picture::from_bytes_lossy(&bytes).unwrap();
+        assert_eq!(lossy.picture_type, PictureType::CoverFront);
+        assert_eq!(lossy.mime_type, "image/png");
+        assert_eq!(lossy.description, "\u{fffd}over");
+        assert_eq!(lossy.data, picture.data);
+    }
+
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn new_checked_valid_mime_type() {
+        let picture = Picture::new_checked(
+            PictureType::CoverFront,
+            "image/png".into(),
+            String::new(),
+            PNG_SIGNATURE.to_vec(),
+        )
+        .unwrap();
+        assert_eq!(picture.mime_type, "image/png");
+
+        // unrecognizable data is not checked against the mime type
+        assert!(
+            Picture::new_checked(
+                PictureType::Other,
+                "image/x-custom".into(),
+                String::new(),
+                vec![0x42; 16],
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn new_checked_malformed_mime_type() {
+        for mime_type in ["notamime", "image/", "/png", "image/png/x", "image /png"] {
+            assert!(matches!(
+                Picture::new_checked(
+                    PictureType::Other,
+                    mime_type.into(),
+                    String::new(),
+                    PNG_SIGNATURE.to_vec(),
+                ),
+                Err(PictureError::InvalidMimeType)
+            ));
+        }
+        assert!(matches!(
+            Picture::new_checked(PictureType::Other, String::new(), String::new(), vec![1]),
+            Err(PictureError::EmptyMimeType)
+        ));
+    }
+
+    #[test]
+    fn new_checked_mime_type_mismatch() {
+        assert!(matches!(
+            Picture::new_checked(
+                PictureType::Other,
+                "image/jpeg".into(),
+                String::new(),
+                PNG_SIGNATURE.to_vec(),
+            ),
+            Err(PictureError::MimeMismatch)
+        ));
+    }
+
+    #[test]
+    fn decode_base64_variants() {
+        // the encoded data contains both "+" and "/", and needs padding
+        let picture = Picture {
+            picture_type: PictureType::CoverFront,
+            mime_type: "image/png".into(),
+            description: "ab".into(),
+            data: vec![0xfb, 0xff, 0xbf, 0x00],
+            ..Picture::new()
+        };
+        let bytes = picture.to_bytes().unwrap();
+        let standard = picture.to_base64().unwrap();
+        assert!(standard.contains(['+', '/']) && standard.ends_with('='));
+
+        for encoded in [
+            standard,
+            BASE64_STANDARD_NO_PAD.encode(&bytes),
+            BASE64_URL_SAFE.encode(&bytes),
+            BASE64_URL_SAFE_NO_PAD.encode(&bytes),
+        ] {
+            let decoded = Picture::from_base64(&encoded).unwrap();
+            assert_eq!(decoded.to_bytes().unwrap(), bytes);
+            assert_eq!(decoded.data, picture.data);
+        }
+
+        assert!(matches!(
+            Picture::from_base64("not base64!"),
+            Err(crate::Error::PictureError(PictureError::Base64DecodeError(
+                _
+            )))
+        ));
+    }
+
+    #[test]
+    fn description_limit() {
+        let picture = Picture {
+            description: "Café au lait".into(),
+            ..Picture::new()
+        };
+        // the limit falls in the middle of the two-byte "é"
+        let limited = picture.clone().with_description_limit(4);
+        assert_eq!(limited.description, "Caf");
+        assert_eq!(
+            Picture::from_bytes(&limited.to_bytes().unwrap())
+                .unwrap()
+                .description,
+            "Caf"
+        );
+
+        let limited = picture.clone().with_description_limit(5);
+        assert_eq!(limited.description, "Café");
+        let limited = picture.clone().with_description_limit(100);
+        assert_eq!(limited.description, picture.description);
+    }
+
+    #[test]
+    fn mime_matches_data() {
+        let mut picture = Picture {
+            mime_type: "image/png".into(),
+            data: PNG_SIGNATURE.to_vec(),
+            ..Picture::new()
+        };
+        assert!(picture.mime_matches_data());
+
+        picture.mime_type = "image/jpeg".into();
+        assert!(!picture.mime_matches_data());
+
+        // the format of the data can't be detected, so no mismatch can be proven
+        picture.data = b"not an image".to_vec();
+        assert!(picture.mime_matches_data());
+    }
+
+    fn picture_with_data(data: Vec<u8>) -> Picture {
+        Picture {
+            data,
+            ..Picture::new()
+        }
+    }
+
+    #[test]
+    fn decode_png_dimensions() {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        data.extend_from_slice(&640u32.to_be_bytes());
+        data.extend_from_slice(&480u32.to_be_bytes());
+        data.extend_from_slice(&[8, 2, 0, 0, 0]);
+        let picture = picture_with_data(data);
+        assert_eq!(picture.decode_dimensions(), Some((640, 480)));
+
+        let mut reader = picture.data_reader();
+        let mut signature = [0; 4];
+        reader.read_exact(&mut signature).unwrap();
+        assert_eq!(&signature, b"\x89PNG");
+    }
+
+    #[test]
+    fn decode_jpeg_dimensions() {
+        let data = [
+            // SOI
+            &[0xff, 0xd8][..],
+            // APP0, with a 16 byte segment
+            &[0xff, 0xe0, 0x00, 0x10],
+            b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0",
+            // SOF0: length, precision, height 600, width 800, components
+            &[0xff, 0xc0, 0x00, 0x11, 0x08, 0x02, 0x58, 0x03, 0x20, 0x03],
+            &[0; 9],
+        ]
+        .concat();
+        let picture = picture_with_data(data);
+        assert_eq!(picture.decode_dimensions(), Some((800, 600)));
+
+        // truncated before the start-of-frame segment
+        let picture = picture_with_data(picture.data[..20].to_vec());
+        assert_eq!(picture.decode_dimensions(), None);
+    }
+
+    #[test]
+    fn read_with_supplied_mime_type() {
+        // not a recognizable image, but the supplied mime type is trusted
+        let data = [0x42; 64];
+        let picture = Picture::read_from(&data[..], Some("image/png".into())).unwrap();
+        assert_eq!(picture.mime_type, "image/png");
+        assert_eq!(picture.data, data);
+    }
+
+    #[test]
+    fn sniff_only_leading_bytes() {
+        let data = vec![0x42; MIME_SNIFF_LIMIT * 4];
+        let reader = FailAfter {
+            data: &data,
+            limit: MIME_SNIFF_LIMIT,
+        };
+        // fails with NoMimeType before the rest of the input is read
+        let result = Picture::read_from(reader, None);
+        assert!(matches!(
+            result,
+            Err(crate::Error::PictureError(PictureError::NoMimeType))
+        ));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn decode_image() {
+        let file = std::fs::File::open("testfiles/silence_cover.opus").unwrap();
+        let tag = crate::Tag::read_from(file).unwrap();
+        let picture = tag.get_picture_type(PictureType::CoverFront).unwrap();
+        assert_eq!(picture.mime_type, "image/png");
+
+        let image = picture.decode_image().unwrap();
+        assert_eq!(
+            Some((image.width(), image.height())),
+            picture.decode_dimensions()
+        );
+    }
+
+    #[test]
+    fn dimensions_round_trip() {
+        let picture = Picture {
+            mime_type: "image/gif".into(),
+            width: 640,
+            height: 480,
+            depth: 8,
+            num_colors: 256,
+            data: vec![1, 2, 3, 4],
+            ..Picture::new()
+        };
+        let decoded = Picture::from_bytes(&picture.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            (
+                decoded.width,
+                decoded.height,
+                decoded.depth,
+                decoded.num_colors
+            ),
+            (640, 480, 8, 256)
+        );
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn from_image() {
+        let image = image::DynamicImage::new_rgb8(3, 2);
+        let picture =
+            Picture::from_image(&image, image::ImageFormat::Png, PictureType::CoverFront).unwrap();
+        assert_eq!(picture.mime_type, "image/png");
+        assert_eq!((picture.width, picture.height, picture.depth), (3, 2, 24));
+
+        let mut tag = crate::Tag::default();
+        tag.add_picture(&picture).unwrap();
+        let embedded = tag.get_picture_type(PictureType::CoverFront).unwrap();
+        assert_eq!((embedded.width, embedded.height), (3, 2));
+        assert_eq!(embedded.decode_dimensions(), Some((3, 2)));
+        assert_eq!(embedded.decode_image().unwrap(), image);
+    }
+}
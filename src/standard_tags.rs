@@ -0,0 +1,18 @@
+/// The most common comment fields, for setting several of them at once with
+/// [`Tag::apply_standard`](crate::Tag::apply_standard).
+///
+/// Fields which are `None` are left untouched when applied, and fields which are `Some` replace
+/// all existing entries of their key.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct StandardTags {
+    /// The `TITLE` field.
+    pub title: Option<String>,
+    /// The `ARTIST` field.
+    pub artist: Option<String>,
+    /// The `ALBUM` field.
+    pub album: Option<String>,
+    /// The `DATE` field.
+    pub date: Option<String>,
+    /// The `TRACKNUMBER` field.
+    pub track: Option<u32>,
+}
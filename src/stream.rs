@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{Error, Result};
+
+/// Diagnostic information about the layout of an ogg stream.
+///
+/// See [`Tag::stream_stats`](crate::Tag::stream_stats).
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct StreamStats {
+    /// The number of ogg pages in the physical stream.
+    pub pages: usize,
+    /// The number of packets which end in the physical stream.
+    pub packets: usize,
+    /// The (zero-based) index of the page the comment header of the first logical stream ends on.
+    pub comment_header_page: usize,
+}
+
+/// The header of an ogg page.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc3533#section-6> for more information.
+#[derive(Debug, Clone)]
+pub struct PageHeader {
+    pub granule_position: u64,
+    pub serial: u32,
+    pub lacing: Vec<u8>,
+}
+
+impl PageHeader {
+    /// Reads a page header, leaving the reader at the start of the page body. Returns None if the
+    /// reader is already at the end of the stream.
+    pub fn read_from<R: Read>(mut f_in: R) -> Result<Option<Self>> {
+        let mut buffer = [0; 27];
+        if f_in.read(&mut buffer[..1])? == 0 {
+            return Ok(None);
+        }
+        f_in.read_exact(&mut buffer[1..])?;
+        if !buffer.starts_with(b"OggS") {
+            return Err(ogg::OggReadError::NoCapturePatternFound.into());
+        }
+
+        let granule_position =
+            u64::from_le_bytes(buffer[6..14].try_into().expect("slice has length 8"));
+        let serial = u32::from_le_bytes(buffer[14..18].try_into().expect("slice has length 4"));
+        let mut lacing = vec![0; usize::from(buffer[26])];
+        f_in.read_exact(&mut lacing)?;
+
+        Ok(Some(Self {
+            granule_position,
+            serial,
+            lacing,
+        }))
+    }
+
+    /// The length of the page body in bytes.
+    pub fn body_len(&self) -> usize {
+        self.lacing.iter().copied().map(usize::from).sum()
+    }
+
+    /// The number of packets which end on this page.
+    pub fn packets_ended(&self) -> usize {
+        self.lacing.iter().filter(|&&len| len < 255).count()
+    }
+}
+
+/// Lookup table for the CRC-32 variant used by ogg (polynomial `0x04c11db7`, no reflection).
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i: u32 = 0;
+    while i < 256 {
+        let mut crc = i << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x04c1_1db7
+            };
+            bit += 1;
+        }
+        table[i as usize] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Recomputes and stores the checksum of a complete ogg page (header and body), for use after
+/// modifying the page in place.
+pub fn update_checksum(page: &mut [u8]) {
+    let crc = checksum(page);
+    page[22..26].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// Computes the checksum of a complete ogg page, treating the stored checksum as zero.
+fn checksum(page: &[u8]) -> u32 {
+    page.iter().enumerate().fold(0, |crc: u32, (index, &byte)| {
+        let byte = if (22..26).contains(&index) { 0 } else { byte };
+        (crc << 8) ^ CRC_TABLE[usize::from((crc >> 24) as u8 ^ byte)]
+    })
+}
+
+/// Checks that every page of a serialized ogg stream is well-formed: it must be complete, its
+/// checksum must match, and it must be flagged as continued exactly if the previous page of the
+/// same logical stream ended in the middle of a packet. This catches oversized packets (such as
+/// a comment header with lots of embedded artwork) being split into pages incorrectly.
+pub fn verify_pages(data: &[u8]) -> Result<()> {
+    let mut continues_packet: HashMap<u32, bool> = HashMap::new();
+    let mut position = 0;
+    let mut page = 0;
+
+    while position < data.len() {
+        let invalid = || Error::InvalidPage { page };
+        let header = PageHeader::read_from(&data[position..])
+            .map_err(|_| invalid())?
+            .ok_or_else(invalid)?;
+        let page_len = 27 + header.lacing.len() + header.body_len();
+        let page_data = data
+            .get(position..position + page_len)
+            .ok_or_else(invalid)?;
+
+        let stored_crc =
+            u32::from_le_bytes(page_data[22..26].try_into().expect("slice has length 4"));
+        let continued = page_data[5] & 0x01 != 0;
+        let expected_continued = continues_packet
+            .insert(header.serial, header.lacing.last() == Some(&255))
+            .unwrap_or(false);
+        if stored_crc != checksum(page_data) || continued != expected_continued {
+            return Err(invalid());
+        }
+
+        position += page_len;
+        page += 1;
+    }
+
+    Ok(())
+}
+
+/// Skips an `ID3v2` tag at the current position of the reader, which some tools prepend to ogg
+/// files. If there is no `ID3v2` tag, the reader is left where it was.
+///
+/// See <https://id3.org/id3v2.4.0-structure> for more information.
+pub fn skip_id3v2<R: Read + Seek>(mut f_in: R) -> Result<()> {
+    let start = f_in.stream_position()?;
+    let mut header = Vec::with_capacity(10);
+    (&mut f_in).take(10).read_to_end(&mut header)?;
+
+    // the size is a 28 bit synchsafe integer, with the top bit of every byte cleared
+    if header.len() == 10 && header.starts_with(b"ID3") && header[6..].iter().all(|b| b & 0x80 == 0)
+    {
+        let size = header[6..]
+            .iter()
+            .fold(0, |size, &byte| (size << 7) | u64::from(byte));
+        let footer = if header[5] & 0x10 == 0 { 0 } else { 10 };
+        f_in.seek(SeekFrom::Start(start + 10 + size + footer))?;
+    } else {
+        f_in.seek(SeekFrom::Start(start))?;
+    }
+    Ok(())
+}
+
+/// Checks whether the reader ends before a complete page header, i.e. whether it is empty or cut
+/// off within the header of its first page. The reader is left where it was.
+pub fn ends_within_page_header<R: Read + Seek>(mut f_in: R) -> Result<bool> {
+    let start = f_in.stream_position()?;
+    let mut header = Vec::with_capacity(27);
+    (&mut f_in).take(27).read_to_end(&mut header)?;
+    f_in.seek(SeekFrom::Start(start))?;
+
+    let pattern_len = header.len().min(4);
+    Ok(header.len() < 27 && header[..pattern_len] == b"OggS"[..pattern_len])
+}
+
+/// Errors with [`Error::NotOggContainer`] if the reader is at the start of a Matroska or `WebM`
+/// file, i.e. at an EBML header. The reader is left where it was.
+pub fn reject_matroska<R: Read + Seek>(mut f_in: R) -> Result<()> {
+    let start = f_in.stream_position()?;
+    let mut magic = Vec::with_capacity(4);
+    (&mut f_in).take(4).read_to_end(&mut magic)?;
+    f_in.seek(SeekFrom::Start(start))?;
+    if magic == [0x1a, 0x45, 0xdf, 0xa3] {
+        return Err(Error::NotOggContainer);
+    }
+    Ok(())
+}
+
+pub fn stream_stats<R: Read + Seek>(mut f_in: R) -> Result<StreamStats> {
+    let mut stats = StreamStats::default();
+    let mut first_serial = None;
+    let mut first_stream_packets = 0;
+    let mut comment_header_page = None;
+
+    while let Some(header) = PageHeader::read_from(&mut f_in)? {
+        let packets_ended = header.packets_ended();
+        if comment_header_page.is_none()
+            && *first_serial.get_or_insert(header.serial) == header.serial
+        {
+            first_stream_packets += packets_ended;
+            if first_stream_packets >= 2 {
+                comment_header_page = Some(stats.pages);
+            }
+        }
+        stats.pages += 1;
+        stats.packets += packets_ended;
+        f_in.seek_relative(header.body_len().try_into()?)?;
+    }
+
+    stats.comment_header_page = comment_header_page.ok_or(crate::Error::MissingPacket)?;
+    Ok(stats)
+}
+
+/// Walks the pages of an ogg stream and returns the granule position of the last page of the
+/// first opus logical stream on which a packet ends.
+pub fn last_granule_position<R: Read + Seek>(mut f_in: R) -> Result<u64> {
+    let mut opus_serial = None;
+    let mut granule_position = None;
+
+    while let Some(header) = PageHeader::read_from(&mut f_in)? {
+        let body_len: i64 = header.body_len().try_into()?;
+        if opus_serial.is_none() {
+            let mut magic = Vec::with_capacity(8);
+            (&mut f_in).take(8).read_to_end(&mut magic)?;
+            if magic == b"OpusHead" {
+                opus_serial = Some(header.serial);
+            }
+            f_in.seek_relative(body_len - i64::try_from(magic.len())?)?;
+        } else {
+            f_in.seek_relative(body_len)?;
+        }
+
+        // a granule position of -1 marks a page on which no packet ends
+        if opus_serial == Some(header.serial) && header.granule_position != u64::MAX {
+            granule_position = Some(header.granule_position);
+        }
+    }
+
+    granule_position.ok_or(Error::MissingPacket)
+}
+
+/// A reader which keeps a copy of everything read through it.
+struct TeeReader<R> {
+    inner: R,
+    data: Vec<u8>,
+}
+
+impl<R: Read> Read for TeeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.data.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+}
+
+/// Reads whole pages from a reader until the comment header of the first opus stream has been
+/// read, and returns the raw bytes of those pages. Nothing past the page the comment header ends
+/// on is read.
+pub fn read_header_pages<R: Read>(f_in: R) -> Result<Vec<u8>> {
+    let mut reader = TeeReader {
+        inner: f_in,
+        data: Vec::new(),
+    };
+    let mut opus_serial = None;
+    let mut opus_packets = 0;
+
+    while let Some(header) = PageHeader::read_from(&mut reader)? {
+        let body_start = reader.data.len();
+        let mut body = vec![0; header.body_len()];
+        reader.read_exact(&mut body)?;
+
+        if opus_serial.is_none() && reader.data[body_start..].starts_with(b"OpusHead") {
+            opus_serial = Some(header.serial);
+        }
+        if opus_serial == Some(header.serial) {
+            opus_packets += header.packets_ended();
+            if opus_packets >= 2 {
+                break;
+            }
+        }
+    }
+
+    Ok(reader.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a page from its header type flags and lacing values, with a zeroed body.
+    fn page(serial: u32, sequence: u32, header_type: u8, lacing: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS\0".to_vec();
+        page.push(header_type);
+        page.extend_from_slice(&0u64.to_le_bytes());
+        page.extend_from_slice(&serial.to_le_bytes());
+        page.extend_from_slice(&sequence.to_le_bytes());
+        page.extend_from_slice(&[0; 4]);
+        page.push(u8::try_from(lacing.len()).unwrap());
+        page.extend_from_slice(lacing);
+        page.resize(
+            page.len() + lacing.iter().map(|&l| usize::from(l)).sum::<usize>(),
+            0,
+        );
+        update_checksum(&mut page);
+        page
+    }
+
+    #[test]
+    fn verify_continued_pages() {
+        let valid = [
+            page(1, 0, 0x02, &[19]),
+            page(1, 1, 0x00, &[255, 255]),
+            page(1, 2, 0x01, &[255, 10]),
+            page(1, 3, 0x00, &[3]),
+        ]
+        .concat();
+        assert!(verify_pages(&valid).is_ok());
+
+        // the third page continues a packet, but isn't flagged as such
+        let unflagged = [
+            page(1, 0, 0x02, &[19]),
+            page(1, 1, 0x00, &[255, 255]),
+            page(1, 2, 0x00, &[255, 10]),
+        ]
+        .concat();
+        assert!(matches!(
+            verify_pages(&unflagged),
+            Err(Error::InvalidPage { page: 2 })
+        ));
+
+        let mut corrupted = valid.clone();
+        *corrupted.last_mut().unwrap() ^= 0xff;
+        assert!(matches!(
+            verify_pages(&corrupted),
+            Err(Error::InvalidPage { page: 3 })
+        ));
+
+        assert!(matches!(
+            verify_pages(&valid[..valid.len() - 1]),
+            Err(Error::InvalidPage { page: 3 })
+        ));
+    }
+}
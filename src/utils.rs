@@ -60,6 +60,57 @@ impl<S: AsRef<str>> From<S> for LowercaseString<'static> {
     }
 }
 
+/// Truncates a string to at most `max_bytes` bytes, cutting it at the last UTF-8 character
+/// boundary within the limit. Returns whether the string was truncated.
+pub fn truncate_at_char_boundary(str: &mut String, max_bytes: usize) -> bool {
+    if str.len() <= max_bytes {
+        return false;
+    }
+    let end = (0..=max_bytes)
+        .rev()
+        .find(|&index| str.is_char_boundary(index))
+        .unwrap_or_default();
+    str.truncate(end);
+    true
+}
+
+/// Escapes a value in the way of `vorbiscomment --escapes`, writing backslashes, line feeds,
+/// carriage returns and NUL characters as `\\`, `\n`, `\r` and `\0`.
+pub fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverses [`escape_value`]. Returns None if the value contains an unknown escape sequence or
+/// ends with a single backslash.
+pub fn unescape_value(value: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next()? {
+            '\\' => '\\',
+            'n' => '\n',
+            'r' => '\r',
+            '0' => '\0',
+            _ => return None,
+        });
+    }
+    Some(unescaped)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;